@@ -19,11 +19,20 @@ int main() {
         printf("Offset: %lu\n", ann.offset);
         printf("Length: %lu\n", ann.length);
         printf("Message: %s\n", ann.message);
+        printf("Provider: %s\n", ann.provider);
+        if (ann.rule_id != NULL) {
+            printf("Rule: %s\n", ann.rule_id);
+        }
         PatronusSuggestionArray* suggs = ann.suggestions;
         if (suggs->len > 0) {
             printf("Suggestions:\n");
             for (uintptr_t s = 0; s < suggs->len; ++s) {
-                printf(" - %s\n", suggs->data[s]);
+                PatronusSuggestion sugg = suggs->data[s];
+                if (sugg.description != NULL) {
+                    printf(" - %s (%s)\n", sugg.replacement, sugg.description);
+                } else {
+                    printf(" - %s\n", sugg.replacement);
+                }
             }
         }
         printf("\n");
@@ -5,13 +5,19 @@ typedef struct SuggestionArray SuggestionArray;
 typedef struct Properties Properties;
 
 typedef enum AnnotationKind {
-    Spelling = 1,
-    Grammar = 2,
-    Style = 3,
-    Typography = 4,
-    Suggestion = 5,
+    PATRONUS_ANNOTATION_KIND_SPELLING = 1,
+    PATRONUS_ANNOTATION_KIND_GRAMMAR = 2,
+    PATRONUS_ANNOTATION_KIND_STYLE = 3,
+    PATRONUS_ANNOTATION_KIND_TYPOGRAPHY = 4,
+    PATRONUS_ANNOTATION_KIND_SUGGESTION = 5,
 } AnnotationKind;
 
+typedef enum Severity {
+    PATRONUS_SEVERITY_ERROR = 1,
+    PATRONUS_SEVERITY_WARNING = 2,
+    PATRONUS_SEVERITY_HINT = 3,
+} Severity;
+
 struct AnnotationArray {
     Annotation* data;
     size_t len;
@@ -19,8 +25,18 @@ struct AnnotationArray {
     void (*cleanup)(Annotation*, size_t, void*);
 };
 
+struct Suggestion {
+    size_t struct_size;
+    char const* replacement;
+    char const* description;
+    float confidence;
+    bool has_range;
+    uintptr_t offset;
+    uintptr_t length;
+};
+
 struct SuggestionArray {
-    char const* const* data;
+    Suggestion* data;
     size_t len;
     void* extra;
     void (*cleanup)(Suggestion*, size_t, void*);
@@ -32,8 +48,20 @@ struct Annotation {
     char const* message;
     AnnotationKind kind;
     SuggestionArray* suggestions;
+    size_t struct_size;
+    char const* rule_id;
+    char const* category;
+    char const* url;
+    char const* provider;
+    Severity severity;
+    float confidence;
 };
 
 struct Properties {
      char const* primary_language;
+     size_t struct_size;
+     char const* const* disabled_rules;
+     uintptr_t disabled_rules_len;
+     char const* const* enabled_rules;
+     uintptr_t enabled_rules_len;
 };
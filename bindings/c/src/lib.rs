@@ -1,9 +1,24 @@
 extern crate patronus;
 extern crate patronus_provider;
 
-pub use patronus_provider::{Annotation, AnnotationArray, Properties, Suggestion};
+pub use patronus_provider::{Annotation, AnnotationArray, Properties, Severity, Suggestion};
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
+use std::ptr;
+
+fn to_c_string(s: &str) -> *const c_char {
+    CString::new(s).expect("cannot create C string").into_raw()
+}
+
+fn to_optional_c_string(s: &Option<String>) -> *const c_char {
+    s.as_ref().map_or(ptr::null(), |s| to_c_string(s))
+}
+
+unsafe fn free_optional_c_string(s: *const c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s as *mut c_char));
+    }
+}
 
 /// Opaque wrapper for `Patronus` struct.
 pub enum Patronus {}
@@ -35,17 +50,29 @@ pub unsafe extern "C" fn patronus_check(
 
     let patronus = &(*(ptr as *mut patronus::Patronus));
 
-    let properties = {
-        let Properties { primary_language } = *props;
-        patronus::Properties {
-            primary_language: CStr::from_ptr(primary_language)
-                .to_string_lossy()
-                .into_owned(),
-        }
+    let properties = patronus::Properties {
+        primary_language: CStr::from_ptr((*props).primary_language)
+            .to_string_lossy()
+            .into_owned(),
     };
 
+    // Rules in the properties apply to this check only,
+    // overriding those configured on the `Patronus` object.
+    let mut rules = patronus.rules.clone();
+    let language = Some(properties.primary_language.as_str());
+    for id in (*props).disabled_rules() {
+        rules.set(&id, false, language);
+    }
+    for id in (*props).enabled_rules() {
+        rules.set(&id, true, language);
+    }
+
     let anns = patronus
-        .check(&properties, &CStr::from_ptr(text).to_string_lossy())
+        .check_with_rules(
+            &properties,
+            &CStr::from_ptr(text).to_string_lossy(),
+            &rules,
+        )
         .iter()
         .map(|&patronus::Annotation {
              offset,
@@ -53,24 +80,45 @@ pub unsafe extern "C" fn patronus_check(
              ref message,
              kind,
              ref suggestions,
+             ref provider,
+             ref rule_id,
+             ref category,
+             ref url,
+             severity,
+             confidence,
+             ..
          }| {
             let msg = CString::new(message.clone())
                 .expect("cannot create C string")
                 .into_raw() as *const c_char;
             let suggestions: Vec<Suggestion> = suggestions
-                .into_iter()
+                .iter()
                 .map(|sugg| {
-                    CString::new((*sugg).clone())
-                        .expect("cannot create C string")
-                        .into_raw() as *const c_char
+                    let range = sugg.range.clone().unwrap_or(0..0);
+                    Suggestion {
+                        description: to_optional_c_string(&sugg.description),
+                        confidence: sugg.confidence.unwrap_or(-1.0),
+                        has_range: sugg.range.is_some(),
+                        offset: range.start,
+                        length: range.end - range.start,
+                        ..Suggestion::new(to_c_string(&sugg.replacement))
+                    }
                 })
                 .collect();
             Annotation {
-                offset: offset,
-                length: length,
-                message: msg,
-                kind: kind,
-                suggestions: Box::into_raw(Box::new(suggestions.into())),
+                rule_id: to_optional_c_string(rule_id),
+                category: to_optional_c_string(category),
+                url: to_optional_c_string(url),
+                provider: to_c_string(provider),
+                severity: severity,
+                confidence: confidence.unwrap_or(-1.0),
+                ..Annotation::new(
+                    offset,
+                    length,
+                    msg,
+                    kind,
+                    Box::into_raw(Box::new(suggestions.into())),
+                )
             }
         })
         .collect::<Vec<Annotation>>()
@@ -88,9 +136,14 @@ pub unsafe extern "C" fn patronus_free_annotations(ptr: *mut AnnotationArray) {
         let ann = &*anns.data.offset(i as isize);
         let suggs = Box::from_raw(ann.suggestions);
         for i in 0..suggs.len {
-            let sugg = *suggs.data.offset(i as isize);
-            CString::from_raw(sugg as *mut c_char);
+            let sugg = &*suggs.data.offset(i as isize);
+            CString::from_raw(sugg.replacement as *mut c_char);
+            free_optional_c_string(sugg.description);
         }
         CString::from_raw(ann.message as *mut c_char);
+        free_optional_c_string(ann.rule_id);
+        free_optional_c_string(ann.category);
+        free_optional_c_string(ann.url);
+        free_optional_c_string(ann.provider);
     }
 }
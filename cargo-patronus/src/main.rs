@@ -0,0 +1,141 @@
+extern crate patronus;
+extern crate serde_json;
+
+mod report;
+mod workspace;
+
+use patronus::format::{markdown, rustdoc};
+use patronus::{Baseline, Patronus, Properties};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+const USAGE: &str = "usage: cargo patronus [--language <language>] [--baseline <file> [--update-baseline]] [<package or workspace directory>]";
+
+/// Checks doc comments and readmes of a package or of all members of a workspace,
+/// printing the issues found in the format of rustc diagnostics.
+///
+/// With `--baseline`, issues recorded in the baseline file are not reported,
+/// `--update-baseline` records all current issues into it instead.
+fn main() {
+    let mut args = env::args().skip(1).peekable();
+    // Cargo passes the name of the subcommand as the first argument.
+    if args.peek().is_some_and(|arg| arg == "patronus") {
+        args.next();
+    }
+    let mut language = String::from("en-US");
+    let mut root = PathBuf::from(".");
+    let mut baseline_path = None;
+    let mut update_baseline = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--language" | "-l" => match args.next() {
+                Some(value) => language = value,
+                None => usage(),
+            },
+            "--baseline" => match args.next() {
+                Some(value) => baseline_path = Some(PathBuf::from(value)),
+                None => usage(),
+            },
+            "--update-baseline" => update_baseline = true,
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with('-') => usage(),
+            _ => root = PathBuf::from(arg),
+        }
+    }
+
+    if update_baseline && baseline_path.is_none() {
+        usage();
+    }
+    let mut baseline = match baseline_path {
+        Some(ref path) if !update_baseline => Baseline::load(path).unwrap_or_else(|err| {
+            eprintln!("error: cannot read baseline {}: {}", path.display(), err);
+            process::exit(2);
+        }),
+        _ => Baseline::new(),
+    };
+
+    let packages = workspace::find_packages(&root).unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        process::exit(2);
+    });
+    // Cargo reports absolute paths, they are shown relative to the root.
+    let root = fs::canonicalize(&root).unwrap_or(root);
+    let mut checker = Patronus::new();
+    let properties = Properties {
+        primary_language: language,
+    };
+
+    let mut issues = 0;
+    for package in &packages {
+        // Word lists in `.patronus` directories of the package and its ancestors.
+        if let Err(err) = checker.load_project_dictionary(&package.directory) {
+            eprintln!(
+                "warning: cannot read project dictionary of {}: {}",
+                package.directory.display(),
+                err
+            );
+        }
+        for file in &package.files {
+            let source = match fs::read_to_string(file) {
+                Ok(source) => source,
+                Err(err) => {
+                    eprintln!("error: cannot read {}: {}", file.display(), err);
+                    process::exit(2);
+                }
+            };
+            let fragments = if file.extension().is_some_and(|extension| extension == "rs") {
+                rustdoc::extract(&source)
+            } else {
+                markdown::extract(&source)
+            };
+            let path = file
+                .strip_prefix(&root)
+                .unwrap_or(file)
+                .display()
+                .to_string();
+            let annotations = checker.check_fragments(&properties, &source, &fragments);
+            if update_baseline {
+                baseline.record(&path, &annotations, &source);
+                continue;
+            }
+            for ann in baseline.filter(&path, annotations, &source) {
+                eprintln!("{}", report::render(&path, &source, &ann));
+                issues += 1;
+            }
+        }
+    }
+
+    if update_baseline {
+        let path = baseline_path.expect("baseline path was checked");
+        if let Err(err) = baseline.save(&path) {
+            eprintln!("error: cannot write baseline {}: {}", path.display(), err);
+            process::exit(2);
+        }
+        eprintln!(
+            "recorded {} issue{} in {}",
+            baseline.len(),
+            if baseline.len() == 1 { "" } else { "s" },
+            path.display()
+        );
+        return;
+    }
+
+    if issues > 0 {
+        eprintln!(
+            "warning: found {} issue{} in documentation",
+            issues,
+            if issues == 1 { "" } else { "s" }
+        );
+        process::exit(1);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
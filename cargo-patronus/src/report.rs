@@ -0,0 +1,87 @@
+use patronus::{Annotation, Severity};
+
+/// Formats an annotation like a rustc diagnostic, pointing at the annotated text.
+pub fn render(path: &str, source: &str, ann: &Annotation) -> String {
+    let level = match ann.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Hint => "note",
+    };
+    let line_start = source[..ann.offset]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    let line_end = source[ann.offset..]
+        .find('\n')
+        .map_or(source.len(), |end| ann.offset + end);
+    let line = &source[line_start..line_end].trim_end_matches('\r');
+    let line_number = source[..line_start].matches('\n').count() + 1;
+    let column = source[line_start..ann.offset].chars().count() + 1;
+    let width = source[ann.offset..(ann.offset + ann.length).min(line_end)]
+        .chars()
+        .count()
+        .max(1);
+    let gutter = " ".repeat(line_number.to_string().len());
+
+    let mut label = String::new();
+    if !ann.suggestions.is_empty() {
+        let replacements: Vec<String> = ann
+            .suggestions
+            .iter()
+            .take(3)
+            .map(|sugg| format!("`{}`", sugg.replacement))
+            .collect();
+        label = format!(" help: did you mean {}?", replacements.join(" or "));
+    }
+    let note = match ann.rule_id {
+        Some(ref rule) => format!("`{}` of {}", rule, ann.provider),
+        None => format!("reported by {}", ann.provider),
+    };
+
+    format!(
+        "{level}: {message}\n{gutter}--> {path}:{line_number}:{column}\n{gutter} |\n{line_number} | {line}\n{gutter} | {padding}{carets}{label}\n{gutter} |\n{gutter} = note: {note}\n",
+        message = ann.message,
+        padding = " ".repeat(column - 1),
+        carets = "^".repeat(width),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use patronus::{AnnotationKind, Suggestion};
+
+    #[test]
+    fn test_render() {
+        let source = "fn f() {}\n\n/// Thsi function\nfn g() {}\n";
+        let ann = Annotation {
+            offset: 15,
+            length: 4,
+            message: "Possible spelling mistake".to_string(),
+            kind: AnnotationKind::Spelling,
+            suggestions: vec![Suggestion {
+                replacement: "This".to_string(),
+                description: None,
+                confidence: None,
+                range: None,
+            }],
+            provider: "Enchant".to_string(),
+            contributors: Vec::new(),
+            rule_id: None,
+            category: None,
+            url: None,
+            severity: Severity::Warning,
+            confidence: None,
+        };
+        assert_eq!(
+            render("src/lib.rs", source, &ann),
+            "warning: Possible spelling mistake
+ --> src/lib.rs:3:5
+  |
+3 | /// Thsi function
+  |     ^^^^ help: did you mean `This`?
+  |
+  = note: reported by Enchant
+"
+        );
+    }
+}
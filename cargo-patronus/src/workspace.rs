@@ -0,0 +1,125 @@
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Package to check.
+pub struct Package {
+    /// Directory containing the manifest.
+    pub directory: PathBuf,
+    /// The readme and Rust sources of the package.
+    pub files: Vec<PathBuf>,
+}
+
+/// Finds the packages to check in the package or workspace at `root`.
+///
+/// Packages are listed by `cargo metadata`, so that workspace members, exclusions
+/// and readmes are resolved the same way Cargo resolves them. In a member of a workspace,
+/// only that member is checked.
+pub fn find_packages(root: &Path) -> io::Result<Vec<Package>> {
+    let metadata = metadata(root)?;
+    let root = fs::canonicalize(root)?;
+    let whole_workspace = metadata["workspace_root"]
+        .as_str()
+        .and_then(|workspace_root| fs::canonicalize(workspace_root).ok())
+        .is_some_and(|workspace_root| workspace_root == root);
+    let mut packages = Vec::new();
+    for package in metadata["packages"].as_array().into_iter().flatten() {
+        let manifest = match package["manifest_path"].as_str() {
+            Some(manifest) => PathBuf::from(manifest),
+            None => continue,
+        };
+        if !whole_workspace && manifest.parent() != Some(root.as_path()) {
+            continue;
+        }
+        let directory = match manifest.parent() {
+            Some(directory) => directory.to_path_buf(),
+            None => continue,
+        };
+        let mut files = Vec::new();
+        // Cargo reports the readme relative to the package, `null` when there is none.
+        if let Some(readme) = package["readme"].as_str() {
+            let readme = directory.join(readme);
+            if readme.is_file() {
+                files.push(readme);
+            }
+        }
+        collect_sources(&directory, &directory, &mut files)?;
+        packages.push(Package { directory, files });
+    }
+    Ok(packages)
+}
+
+/// Runs `cargo metadata` for the manifest in `root`, without resolving dependencies.
+fn metadata(root: &Path) -> io::Result<Value> {
+    // Cargo sets `CARGO` for subcommands, so that the same toolchain is used.
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let output = Command::new(cargo)
+        .args([
+            "metadata",
+            "--no-deps",
+            "--format-version",
+            "1",
+            "--manifest-path",
+        ])
+        .arg(root.join("Cargo.toml"))
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    serde_json::from_slice(&output.stdout).map_err(io::Error::other)
+}
+
+/// Collects Rust sources in `dir`, skipping build output, hidden directories and nested packages.
+fn collect_sources(package: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if entry.file_type()?.is_dir() {
+            let skipped = name.starts_with('.')
+                || (dir == package && name == "target")
+                || path.join("Cargo.toml").is_file();
+            if !skipped {
+                collect_sources(package, &path, files)?;
+            }
+        } else if name.ends_with(".rs") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_packages() {
+        let package = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let packages = find_packages(package).unwrap();
+        let package = fs::canonicalize(package).unwrap();
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].directory, package);
+        let names: Vec<_> = packages[0]
+            .files
+            .iter()
+            .map(|file| file.strip_prefix(&package).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                PathBuf::from("src/main.rs"),
+                PathBuf::from("src/report.rs"),
+                PathBuf::from("src/workspace.rs"),
+            ]
+        );
+    }
+}
@@ -1,34 +1,184 @@
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+extern crate unicode_segmentation;
+
 mod macros;
 mod parray;
+pub mod segment;
 
 pub use parray::PArray;
 
+/// Version of the provider interface described by this crate.
+/// Providers should return it from `patronus_provider_version`.
+///
+/// Providers of version 1 are still supported. Since version 2, structures are only extended
+/// by appending fields and their `struct_size` tells which of them the other side knows,
+/// so adding fields does not change the version.
+pub const PROVIDER_VERSION: std::os::raw::c_int = 2;
+
+/// Whether a structure of `struct_size` bytes contains the field at `offset` of type `T`.
+fn has_field<T>(struct_size: usize, offset: usize) -> bool {
+    offset + std::mem::size_of::<T>() <= struct_size
+}
+
 /// Properties of the text to be checked.
 #[derive(Debug)]
 #[repr(C)]
 pub struct Properties {
     pub primary_language: *const std::os::raw::c_char,
+    /// Size of the structure, the fields below are only present when it covers them.
+    /// Zero when the structure only contains `primary_language`.
+    pub struct_size: usize,
+    /// IDs of rules the user turned off, `disabled_rules_len` strings.
+    pub disabled_rules: *const *const std::os::raw::c_char,
+    pub disabled_rules_len: usize,
+    /// IDs of rules the user turned on, including those off by default, `enabled_rules_len` strings.
+    pub enabled_rules: *const *const std::os::raw::c_char,
+    pub enabled_rules_len: usize,
+}
+
+impl Properties {
+    /// Copies the IDs of disabled rules.
+    ///
+    /// # Safety
+    ///
+    /// `disabled_rules` must point to `disabled_rules_len` NUL-terminated strings.
+    pub unsafe fn disabled_rules(&self) -> Vec<String> {
+        if !has_field::<usize>(
+            self.struct_size,
+            std::mem::offset_of!(Properties, disabled_rules_len),
+        ) {
+            return Vec::new();
+        }
+        strings(self.disabled_rules, self.disabled_rules_len)
+    }
+
+    /// Copies the IDs of enabled rules.
+    ///
+    /// # Safety
+    ///
+    /// `enabled_rules` must point to `enabled_rules_len` NUL-terminated strings.
+    pub unsafe fn enabled_rules(&self) -> Vec<String> {
+        if !has_field::<usize>(
+            self.struct_size,
+            std::mem::offset_of!(Properties, enabled_rules_len),
+        ) {
+            return Vec::new();
+        }
+        strings(self.enabled_rules, self.enabled_rules_len)
+    }
+}
+
+unsafe fn strings(data: *const *const std::os::raw::c_char, len: usize) -> Vec<String> {
+    if data.is_null() {
+        return Vec::new();
+    }
+    (0..len)
+        .map(|i| {
+            std::ffi::CStr::from_ptr(*data.add(i))
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect()
 }
 
 /// Type of annotation.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(C)]
 pub enum AnnotationKind {
     Spelling = 1,
     Grammar = 2,
     Style = 3,
     Typography = 4,
+    /// Generic improvement; prefer a specific kind combined with `Severity::Hint`.
     Suggestion = 5,
 }
 
-/// Suggestion string
-pub type Suggestion = *const std::os::raw::c_char;
+/// Severity of annotation, ordered from the most severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(C)]
+pub enum Severity {
+    /// Definite mistake.
+    Error = 1,
+    /// Likely mistake.
+    Warning = 2,
+    /// Optional improvement.
+    Hint = 3,
+}
+
+/// C-ABI compatible suggestion for fixing an annotated issue.
+#[derive(Debug)]
+#[repr(C)]
+pub struct Suggestion {
+    /// Size of the structure, used as the stride of `SuggestionArray`.
+    pub struct_size: usize,
+    /// Text to replace the range with, empty string suggests a deletion.
+    pub replacement: *const std::os::raw::c_char,
+    /// Short explanation of the suggestion, can be null.
+    pub description: *const std::os::raw::c_char,
+    /// Confidence between 0 and 1, negative when unknown.
+    pub confidence: f32,
+    /// Whether `offset` and `length` should be used instead of the annotation’s range.
+    pub has_range: bool,
+    pub offset: usize,
+    pub length: usize,
+}
+
+impl Suggestion {
+    /// Creates a suggestion replacing the annotated range with given text.
+    pub fn new(replacement: *const std::os::raw::c_char) -> Self {
+        Self {
+            struct_size: std::mem::size_of::<Self>(),
+            replacement,
+            description: std::ptr::null(),
+            confidence: -1.0,
+            has_range: false,
+            offset: 0,
+            length: 0,
+        }
+    }
+}
+
+/// C-ABI compatible description of a rule a provider checks.
+#[derive(Debug)]
+#[repr(C)]
+pub struct RuleInfo {
+    /// Size of the structure, used as the stride of `RuleArray`.
+    pub struct_size: usize,
+    /// Identifier matching `Annotation::rule_id`.
+    pub id: *const std::os::raw::c_char,
+    pub description: *const std::os::raw::c_char,
+    /// Human-readable category of the rule, can be null.
+    pub category: *const std::os::raw::c_char,
+    /// Whether the rule is checked unless disabled.
+    pub enabled_by_default: bool,
+}
+
+impl RuleInfo {
+    /// Creates a description of a rule that is enabled by default.
+    pub fn new(id: *const std::os::raw::c_char, description: *const std::os::raw::c_char) -> Self {
+        Self {
+            struct_size: std::mem::size_of::<Self>(),
+            id,
+            description,
+            category: std::ptr::null(),
+            enabled_by_default: true,
+        }
+    }
+}
+
+/// Array of rules
+pub type RuleArray = PArray<RuleInfo>;
 /// Array of suggestions
 pub type SuggestionArray = PArray<Suggestion>;
 /// Array of annotations
 pub type AnnotationArray = PArray<Annotation>;
 
 /// C-ABI compatible `Annotation` struct.
+/// The string fields after `struct_size` are optional and can be null.
 #[derive(Debug)]
 #[repr(C)]
 pub struct Annotation {
@@ -36,7 +186,49 @@ pub struct Annotation {
     pub length: usize,
     pub message: *const std::os::raw::c_char,
     pub kind: AnnotationKind,
+    /// Providers of version 1 return an array of replacement strings instead.
     pub suggestions: *mut SuggestionArray,
+    /// Size of the structure, used as the stride of `AnnotationArray`.
+    /// The fields below are only present when it covers them.
+    pub struct_size: usize,
+    /// Stable identifier of the rule that produced the annotation.
+    pub rule_id: *const std::os::raw::c_char,
+    /// Human-readable category of the rule.
+    pub category: *const std::os::raw::c_char,
+    /// URL with more information about the issue.
+    pub url: *const std::os::raw::c_char,
+    /// Name of the provider that produced the annotation.
+    /// Providers can leave it null, Patronus fills it in.
+    pub provider: *const std::os::raw::c_char,
+    pub severity: Severity,
+    /// Confidence between 0 and 1, negative when unknown.
+    pub confidence: f32,
+}
+
+impl Annotation {
+    /// Creates an annotation without the optional details.
+    pub fn new(
+        offset: usize,
+        length: usize,
+        message: *const std::os::raw::c_char,
+        kind: AnnotationKind,
+        suggestions: *mut SuggestionArray,
+    ) -> Self {
+        Self {
+            offset,
+            length,
+            message,
+            kind,
+            suggestions,
+            struct_size: std::mem::size_of::<Self>(),
+            rule_id: std::ptr::null(),
+            category: std::ptr::null(),
+            url: std::ptr::null(),
+            provider: std::ptr::null(),
+            severity: Severity::Warning,
+            confidence: -1.0,
+        }
+    }
 }
 
 /// Wrapper for provider response allowing automatic cleanup.
@@ -65,9 +257,81 @@ pub struct Provider {
     pub free_annotations: unsafe extern "C" fn(*mut AnnotationArray),
     pub free_provider: unsafe extern "C" fn(*mut Provider),
     pub data: *mut std::os::raw::c_void,
+    /// Size of the structure, the fields below are only present when it covers them.
+    pub struct_size: usize,
+    /// Optional function returning a string that changes whenever the provider’s version
+    /// or configuration changes, so that cached results can be invalidated.
+    /// The string is owned by the provider.
+    pub fingerprint: Option<FingerprintFn>,
+    /// Maximum length in bytes of a text passed to `check`, 0 when unlimited.
+    /// Longer texts are split into chunks by Patronus.
+    pub max_text_length: usize,
+    /// Whether `check` can be called from several threads at once.
+    pub thread_safe: bool,
+    /// Optional function accepting a word of the user’s personal dictionary,
+    /// e.g. by adding it to the provider’s own personal word list.
+    /// Patronus also stores the dictionary itself and teaches the words on every start.
+    pub add_word: Option<WordFn>,
+    /// Optional function forgetting a word earlier passed to `add_word`.
+    pub remove_word: Option<WordFn>,
+    /// Optional function accepting a word until the provider is freed.
+    pub ignore_word: Option<WordFn>,
+    /// Optional function listing the rules available for the language in `props`.
+    /// The result is freed with `free_rules`.
+    pub list_rules: Option<ListRulesFn>,
+    pub free_rules: Option<FreeRulesFn>,
 }
 
+/// Function returning the fingerprint of the provider with given `data`.
+pub type FingerprintFn =
+    unsafe extern "C" fn(data: *mut std::os::raw::c_void) -> *const std::os::raw::c_char;
+
+/// Function listing the rules of the provider with given `data`.
+pub type ListRulesFn = unsafe extern "C" fn(
+    props: *const Properties,
+    data: *mut std::os::raw::c_void,
+) -> *mut RuleArray;
+
+/// Function freeing the result of `ListRulesFn`.
+pub type FreeRulesFn = unsafe extern "C" fn(*mut RuleArray);
+
+/// Function receiving a word and the provider’s `data`.
+/// The word is owned by Patronus and only valid during the call.
+pub type WordFn =
+    unsafe extern "C" fn(word: *const std::os::raw::c_char, data: *mut std::os::raw::c_void);
+
 impl Provider {
+    /// Creates a provider without any of the optional capabilities.
+    pub fn new(
+        name: unsafe extern "C" fn() -> *const std::os::raw::c_char,
+        check: unsafe extern "C" fn(
+            props: *const Properties,
+            text: *const std::os::raw::c_char,
+            data: *mut std::os::raw::c_void,
+        ) -> *mut AnnotationArray,
+        free_annotations: unsafe extern "C" fn(*mut AnnotationArray),
+        free_provider: unsafe extern "C" fn(*mut Provider),
+        data: *mut std::os::raw::c_void,
+    ) -> Self {
+        Self {
+            name,
+            check,
+            free_annotations,
+            free_provider,
+            data,
+            struct_size: std::mem::size_of::<Self>(),
+            fingerprint: None,
+            max_text_length: 0,
+            thread_safe: false,
+            add_word: None,
+            remove_word: None,
+            ignore_word: None,
+            list_rules: None,
+            free_rules: None,
+        }
+    }
+
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
     pub fn check(&self, props: *const Properties, text: *const std::os::raw::c_char) -> Response {
         Response {
             annotations: unsafe { (self.check)(props, text, self.data) },
@@ -1,3 +1,4 @@
+#[allow(clippy::empty_line_after_outer_attr)]
 #[macro_export]
 
 /// Creates a static string (usually stored in [`.rodata`]) and returns a pointer to it (`*const c_char`).
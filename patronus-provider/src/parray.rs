@@ -14,6 +14,7 @@ pub struct PArray<T> {
 }
 
 impl<T> From<Vec<T>> for PArray<T> {
+    #[allow(clippy::redundant_field_names)]
     fn from(mut v: Vec<T>) -> Self {
         v.shrink_to_fit();
         let ptr = v.as_mut_ptr();
@@ -0,0 +1,120 @@
+//! Splitting text into words, sentences and paragraphs.
+//!
+//! Words and sentences follow [Unicode Text Segmentation](https://www.unicode.org/reports/tr29/)
+//! rules, paragraphs are separated by blank lines. All functions return byte ranges
+//! so providers can use them directly as annotation offsets and lengths.
+
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Returns the ranges of words, skipping punctuation and spaces between them.
+///
+/// Apostrophes inside words are kept (“don’t” is a single word), while hyphenated
+/// compounds are split into their parts.
+pub fn words(text: &str) -> Vec<Range<usize>> {
+    text.unicode_word_indices()
+        .map(|(offset, word)| offset..offset + word.len())
+        .collect()
+}
+
+/// Returns the ranges of sentences, without the whitespace surrounding them.
+pub fn sentences(text: &str) -> Vec<Range<usize>> {
+    text.split_sentence_bound_indices()
+        .filter_map(|(offset, sentence)| trimmed(offset, sentence))
+        .collect()
+}
+
+/// Returns the ranges of paragraphs separated by blank lines, without the line breaks ending them.
+pub fn paragraphs(text: &str) -> Vec<Range<usize>> {
+    let mut result = Vec::new();
+    let mut current: Option<Range<usize>> = None;
+    let mut position = 0;
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\n', '\r']);
+        if content.trim().is_empty() {
+            result.extend(current.take());
+        } else {
+            let end = position + content.len();
+            match current {
+                Some(ref mut paragraph) => paragraph.end = end,
+                None => current = Some(position..end),
+            }
+        }
+        position += line.len();
+    }
+    result.extend(current);
+    result
+}
+
+fn trimmed(offset: usize, segment: &str) -> Option<Range<usize>> {
+    let start = segment.len() - segment.trim_start().len();
+    let end = segment.trim_end().len();
+    if start < end {
+        Some(offset + start..offset + end)
+    } else {
+        None
+    }
+}
+
+/// Converts a range measured in UTF-16 code units, as used by Java or JavaScript based checkers,
+/// into a byte range of the text.
+pub fn utf16_to_byte_range(text: &str, offset: usize, length: usize) -> Range<usize> {
+    let mut units = 0;
+    let mut start = text.len();
+    let mut end = text.len();
+    for (position, c) in text.char_indices() {
+        if units == offset {
+            start = position;
+        }
+        if units == offset + length {
+            end = position;
+            break;
+        }
+        units += c.len_utf16();
+    }
+    start.min(end)..end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split<F: Fn(&str) -> Vec<Range<usize>>>(segmenter: F, text: &str) -> Vec<&str> {
+        segmenter(text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect()
+    }
+
+    #[test]
+    fn test_words() {
+        assert_eq!(
+            split(words, "Don’t panic, well-known 3rd-party B2B…  ok"),
+            vec!["Don’t", "panic", "well", "known", "3rd", "party", "B2B", "ok"]
+        );
+    }
+
+    #[test]
+    fn test_sentences() {
+        assert_eq!(
+            split(sentences, "Hello there. How are you?  Fine!\n"),
+            vec!["Hello there.", "How are you?", "Fine!"]
+        );
+    }
+
+    #[test]
+    fn test_paragraphs() {
+        let text = "First line\nsecond line\n\n  \r\nSecond paragraph\n\n";
+        assert_eq!(
+            split(paragraphs, text),
+            vec!["First line\nsecond line", "Second paragraph"]
+        );
+    }
+
+    #[test]
+    fn test_utf16_to_byte_range() {
+        let text = "😀 žluťoučký kůň";
+        assert_eq!(&text[utf16_to_byte_range(text, 3, 9)], "žluťoučký");
+        assert_eq!(&text[utf16_to_byte_range(text, 13, 3)], "kůň");
+    }
+}
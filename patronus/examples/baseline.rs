@@ -0,0 +1,63 @@
+extern crate patronus;
+
+use patronus::{Baseline, Patronus, Properties};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+/// Checks files, reporting only annotations missing from a baseline file.
+/// With `--update`, the baseline is refreshed to contain all current annotations instead.
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let update = args.first().is_some_and(|arg| arg == "--update");
+    if update {
+        args.remove(0);
+    }
+    if args.len() < 3 {
+        eprintln!("usage: baseline [--update] <baseline file> <language> <file>...");
+        process::exit(2);
+    }
+    let baseline_path = Path::new(&args[0]);
+    let properties = Properties {
+        primary_language: args[1].clone(),
+    };
+
+    let baseline = if update || !baseline_path.exists() {
+        Baseline::new()
+    } else {
+        Baseline::load(baseline_path).expect("cannot read baseline")
+    };
+    let mut refreshed = Baseline::new();
+    let checker = Patronus::new();
+    let mut new_annotations = 0;
+
+    for file in &args[2..] {
+        let text = fs::read_to_string(file).expect("cannot read file");
+        let annotations = checker.check(&properties, &text.as_str().into());
+        if update {
+            refreshed.record(file, &annotations, &text);
+            continue;
+        }
+        for ann in baseline.filter(file, annotations, &text) {
+            new_annotations += 1;
+            let line = text[..ann.offset].matches('\n').count() + 1;
+            println!(
+                "{}:{}: {} ({})",
+                file,
+                line,
+                ann.message,
+                ann.rule_id.as_deref().unwrap_or(&ann.provider)
+            );
+        }
+    }
+
+    if update {
+        refreshed
+            .save(baseline_path)
+            .expect("cannot write baseline");
+        println!("recorded {} annotations", refreshed.len());
+    } else if new_annotations > 0 {
+        process::exit(1);
+    }
+}
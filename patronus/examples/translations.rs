@@ -0,0 +1,54 @@
+extern crate patronus;
+
+use patronus::format::translation::{self, Entry};
+use patronus::{Patronus, Properties};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+/// Checks translations in gettext PO, XLIFF and Fluent files, reporting issues per entry.
+/// The language is used for files that do not specify it.
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 2 {
+        eprintln!("usage: translations <language> <file>...");
+        process::exit(2);
+    }
+    let properties = Properties {
+        primary_language: args[0].clone(),
+    };
+    let checker = Patronus::new();
+    let mut issues = 0;
+
+    for file in &args[1..] {
+        let source = fs::read_to_string(file).expect("cannot read file");
+        let extension = Path::new(file).extension().and_then(|ext| ext.to_str());
+        let entries: Vec<Entry> = match extension {
+            Some("po") | Some("pot") => translation::extract_po(&source),
+            Some("xlf") | Some("xliff") => translation::extract_xliff(&source),
+            Some("ftl") => translation::extract_fluent(&source),
+            _ => {
+                eprintln!("{}: unknown translation format", file);
+                continue;
+            }
+        };
+        for issue in checker.check_translations(&properties, &source, &entries) {
+            issues += 1;
+            let ann = &issue.annotation;
+            println!(
+                "{}:{}:{}: [{}] {} ({})",
+                file,
+                issue.line,
+                issue.column,
+                issue.id.replace('\u{4}', "|"),
+                ann.message,
+                ann.rule_id.as_deref().unwrap_or(&ann.provider)
+            );
+        }
+    }
+
+    if issues > 0 {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,196 @@
+use error::Error;
+use provider::segment;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use Annotation;
+
+/// Number of words on each side of an annotation, within its sentence, that identify its location.
+const CONTEXT_WORDS: usize = 3;
+
+/// Snapshot of known annotations, so that only newly introduced ones are reported.
+///
+/// Annotations are identified by the provider, the rule and the surrounding words
+/// rather than offsets, so that they survive edits elsewhere in the text.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Baseline {
+    /// Number of annotations with each fingerprint.
+    fingerprints: BTreeMap<String, usize>,
+}
+
+impl Baseline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a baseline file written by `save`.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path).map_err(|source| Error::IoError { source })?;
+        let mut baseline = Self::new();
+        for line in contents.lines().map(str::trim) {
+            if !line.is_empty() && !line.starts_with('#') {
+                *baseline.fingerprints.entry(line.to_string()).or_default() += 1;
+            }
+        }
+        Ok(baseline)
+    }
+
+    /// Writes the baseline into a file, one fingerprint per line.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let mut contents = String::from("# Patronus baseline, annotations to be ignored\n");
+        for (fingerprint, &count) in &self.fingerprints {
+            for _ in 0..count {
+                contents.push_str(fingerprint);
+                contents.push('\n');
+            }
+        }
+        fs::write(path, contents).map_err(|source| Error::IoError { source })
+    }
+
+    pub fn len(&self) -> usize {
+        self.fingerprints.values().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fingerprints.is_empty()
+    }
+
+    /// Adds annotations of a text to the baseline.
+    /// `scope`, e.g. a file name, distinguishes identical texts in different places.
+    pub fn record(&mut self, scope: &str, annotations: &[Annotation], text: &str) {
+        let segments = Segments::new(text);
+        for ann in annotations {
+            *self
+                .fingerprints
+                .entry(segments.fingerprint(scope, ann))
+                .or_default() += 1;
+        }
+    }
+
+    /// Returns the annotations of a text that are not in the baseline.
+    /// Each recorded annotation excuses at most one occurrence.
+    pub fn filter(&self, scope: &str, annotations: Vec<Annotation>, text: &str) -> Vec<Annotation> {
+        let segments = Segments::new(text);
+        let mut remaining = self.fingerprints.clone();
+        annotations
+            .into_iter()
+            .filter(
+                |ann| match remaining.get_mut(&segments.fingerprint(scope, ann)) {
+                    Some(count) if *count > 0 => {
+                        *count -= 1;
+                        false
+                    }
+                    _ => true,
+                },
+            )
+            .collect()
+    }
+}
+
+/// Words and sentences of a text.
+struct Segments<'a> {
+    text: &'a str,
+    words: Vec<Range<usize>>,
+    sentences: Vec<Range<usize>>,
+}
+
+impl<'a> Segments<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            words: segment::words(text),
+            sentences: segment::sentences(text),
+        }
+    }
+
+    /// Hashes what identifies an annotation independently of its offset.
+    fn fingerprint(&self, scope: &str, ann: &Annotation) -> String {
+        let text = self.text;
+        let end = ann.offset + ann.length;
+        let sentence = self
+            .sentences
+            .get(self.sentences.partition_point(|s| s.end <= ann.offset))
+            .filter(|sentence| sentence.start <= ann.offset)
+            .cloned()
+            .unwrap_or(ann.offset..end);
+        let words = |range: Range<usize>| -> String {
+            range
+                .filter_map(|i| self.words.get(i))
+                .filter(|word| sentence.start <= word.start && word.end <= sentence.end)
+                .map(|word| &text[word.clone()])
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let before = self.words.partition_point(|word| word.end <= ann.offset);
+        let after = self.words.partition_point(|word| word.start < end);
+        let annotated = text
+            .get(ann.offset..end)
+            .unwrap_or_default()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut hasher = Sha256::new();
+        for part in &[
+            scope,
+            &ann.provider,
+            ann.rule_id.as_deref().unwrap_or(&ann.message),
+            &words(before.saturating_sub(CONTEXT_WORDS)..before),
+            &annotated,
+            &words(after..after + CONTEXT_WORDS),
+        ] {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part.as_bytes());
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tests::annotation;
+
+    #[test]
+    fn test_filter() {
+        let text = "Tou manny misteaks.";
+        let mut baseline = Baseline::new();
+        baseline.record(
+            "README.md",
+            &[annotation("Enchant", 0, 3), annotation("Enchant", 4, 5)],
+            text,
+        );
+        assert_eq!(baseline.len(), 2);
+
+        // Moved by an edit before, with a new mistake after.
+        let text = "Oh. Tou manny misteaks.";
+        let anns = vec![
+            annotation("Enchant", 4, 3),
+            annotation("Enchant", 8, 5),
+            annotation("Enchant", 14, 8),
+        ];
+        let new = baseline.filter("README.md", anns.clone(), text);
+        assert_eq!(new.len(), 1);
+        assert_eq!(new[0].offset, 14);
+
+        assert_eq!(baseline.filter("other.md", anns, text).len(), 3);
+    }
+
+    #[test]
+    fn test_file() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("baseline.txt");
+        let mut baseline = Baseline::new();
+        baseline.record("", &[annotation("Enchant", 0, 2)], "ab ab");
+        baseline.record("", &[annotation("Enchant", 0, 2)], "ab ab");
+        baseline.save(&path).unwrap();
+        assert_eq!(Baseline::load(&path).unwrap(), baseline);
+        assert_eq!(baseline.len(), 2);
+    }
+}
@@ -0,0 +1,345 @@
+use error::Error;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tempfile::NamedTempFile;
+use {Annotation, Properties};
+
+/// Default limit of the size of the disk store in bytes.
+const DEFAULT_DISK_LIMIT: u64 = 64 * 1024 * 1024;
+
+/// In-memory store evicting the least recently used entries.
+struct Lru {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<String, (u64, Vec<Annotation>)>,
+    /// Keys ordered by the time of last use.
+    usage: BTreeMap<u64, String>,
+}
+
+impl Lru {
+    fn touch(&mut self, key: &str) -> Option<&Vec<Annotation>> {
+        self.tick += 1;
+        let tick = self.tick;
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                let key = self.usage.remove(&entry.0).expect("entry is not tracked");
+                self.usage.insert(tick, key);
+                entry.0 = tick;
+                Some(&entry.1)
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&mut self, key: String, annotations: Vec<Annotation>) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if let Some((tick, _)) = self.entries.remove(&key) {
+            self.usage.remove(&tick);
+        }
+        while self.entries.len() >= self.capacity {
+            let oldest = *self.usage.keys().next().expect("cache is empty");
+            let key = self.usage.remove(&oldest).expect("entry is not tracked");
+            self.entries.remove(&key);
+        }
+        self.usage.insert(self.tick, key.clone());
+        self.entries.insert(key, (self.tick, annotations));
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.usage.clear();
+    }
+}
+
+/// Cache of annotations produced by providers.
+///
+/// Entries are keyed by the checked paragraph, properties and identity of the provider,
+/// which includes its configuration, so they are invalidated when any of them changes.
+/// Recently used results are kept in memory, optionally backed by files in a directory
+/// whose size is limited by evicting the least recently used files.
+pub struct Cache {
+    memory: Mutex<Lru>,
+    directory: Option<PathBuf>,
+    /// Maximum size of the files in `directory` in bytes.
+    disk_limit: u64,
+    /// Size of the files in `directory`, `None` until measured.
+    disk_usage: Mutex<Option<u64>>,
+}
+
+impl Cache {
+    /// Creates a cache holding up to `capacity` results in memory.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            memory: Mutex::new(Lru {
+                capacity,
+                tick: 0,
+                entries: HashMap::new(),
+                usage: BTreeMap::new(),
+            }),
+            directory: None,
+            disk_limit: DEFAULT_DISK_LIMIT,
+            disk_usage: Mutex::new(None),
+        }
+    }
+
+    /// Limits the size of the disk store, 64 MiB by default.
+    pub fn set_disk_limit(&mut self, bytes: u64) {
+        self.disk_limit = bytes;
+    }
+
+    /// Creates a cache that additionally stores results in given directory.
+    pub fn with_directory(capacity: usize, directory: PathBuf) -> Self {
+        Self {
+            directory: Some(directory),
+            ..Self::new(capacity)
+        }
+    }
+
+    /// Creates a cache that additionally stores results in `patronus` XDG cache directory.
+    pub fn with_xdg_directory(capacity: usize) -> Result<Self, Error> {
+        let xdg_dirs =
+            xdg::BaseDirectories::with_prefix("patronus").map_err(|err| Error::ConfigError {
+                message: err.to_string(),
+            })?;
+        let directory = xdg_dirs
+            .create_cache_directory("results")
+            .map_err(|source| Error::IoError { source })?;
+        Ok(Self::with_directory(capacity, directory))
+    }
+
+    /// Computes the key under which results of checking given text are stored.
+    pub fn key(text: &str, props: &Properties, provider_identity: &str) -> String {
+        let mut hasher = Sha256::new();
+        for part in &[provider_identity, &props.primary_language, text] {
+            // Prefix the parts with their length so that they cannot run into each other.
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part.as_bytes());
+        }
+        hex(&hasher.finalize())
+    }
+
+    /// Path of the file storing an entry. Any key can be used, so it is hashed
+    /// to get a file name that stays within the directory.
+    fn path(&self, key: &str) -> Option<PathBuf> {
+        self.directory.as_ref().map(|directory| {
+            let name = hex(&Sha256::digest(key.as_bytes()));
+            directory.join(&name[..2]).join(format!("{}.json", name))
+        })
+    }
+
+    /// Looks up the annotations stored under a key.
+    pub fn get(&self, key: &str) -> Option<Vec<Annotation>> {
+        let mut memory = self.memory.lock().expect("cache lock is poisoned");
+        if let Some(annotations) = memory.touch(key) {
+            return Some(annotations.clone());
+        }
+
+        let path = self.path(key)?;
+        let contents = fs::read(&path).ok()?;
+        // Unreadable entries, e.g. from an older version, are treated as missing.
+        let annotations: Vec<Annotation> = serde_json::from_slice(&contents).ok()?;
+        // Eviction removes the files that were not used for the longest time.
+        let _ = fs::File::options()
+            .append(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()));
+        memory.insert(key.to_string(), annotations.clone());
+        Some(annotations)
+    }
+
+    /// Stores annotations under a key.
+    /// Failure to write the disk store is ignored, the results are still kept in memory.
+    pub fn insert(&self, key: &str, annotations: &[Annotation]) {
+        if let Some(path) = self.path(key) {
+            let write = || -> Result<u64, Box<dyn std::error::Error>> {
+                let parent = path.parent().expect("entry has a parent directory");
+                fs::create_dir_all(parent)?;
+                let contents = serde_json::to_vec(annotations)?;
+                // Each writer uses its own temporary file so that concurrent writers
+                // cannot interleave, and readers only see complete entries.
+                let mut temporary = NamedTempFile::new_in(parent)?;
+                temporary.write_all(&contents)?;
+                temporary.persist(&path)?;
+                Ok(contents.len() as u64)
+            };
+            if let Ok(written) = write() {
+                self.record_write(written);
+            }
+        }
+
+        self.memory
+            .lock()
+            .expect("cache lock is poisoned")
+            .insert(key.to_string(), annotations.to_vec());
+    }
+
+    /// Keeps track of the size of the disk store, evicting files when it exceeds the limit.
+    fn record_write(&self, written: u64) {
+        let directory = match self.directory {
+            Some(ref directory) => directory,
+            None => return,
+        };
+        let mut usage = self.disk_usage.lock().expect("cache lock is poisoned");
+        let current = match *usage {
+            Some(usage) => usage + written,
+            None => evict(directory, u64::MAX),
+        };
+        *usage = Some(if current > self.disk_limit {
+            // Make some room so that eviction does not run on every write.
+            evict(directory, self.disk_limit / 4 * 3)
+        } else {
+            current
+        });
+    }
+
+    /// Removes all stored results.
+    pub fn clear(&self) -> Result<(), Error> {
+        self.memory.lock().expect("cache lock is poisoned").clear();
+        *self.disk_usage.lock().expect("cache lock is poisoned") = None;
+        if let Some(ref directory) = self.directory {
+            if directory.exists() {
+                fs::remove_dir_all(directory).map_err(|source| Error::IoError { source })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Removes the least recently used files of the disk store until it is at most `limit` bytes,
+/// returns the resulting size.
+fn evict(directory: &Path, limit: u64) -> u64 {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(directory).into_iter().flatten().flatten() {
+        for file in fs::read_dir(entry.path()).into_iter().flatten().flatten() {
+            if let Ok(metadata) = file.metadata() {
+                if metadata.is_file() {
+                    let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                    files.push((modified, metadata.len(), file.path()));
+                }
+            }
+        }
+    }
+    let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+    files.sort();
+    for (_, size, path) in files {
+        if total <= limit {
+            break;
+        }
+        if fs::remove_file(path).is_ok() {
+            total -= size;
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tests::{self, annotation};
+    use AnnotationKind;
+
+    fn props(lang: &str) -> Properties {
+        Properties {
+            primary_language: lang.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_key() {
+        let key = Cache::key("text", &props("en"), "Enchant");
+        assert_eq!(key, Cache::key("text", &props("en"), "Enchant"));
+        assert_ne!(key, Cache::key("text", &props("cs"), "Enchant"));
+        assert_ne!(key, Cache::key("text", &props("en"), "LanguageTool"));
+        assert_ne!(
+            Cache::key("ab", &props("c"), "Enchant"),
+            Cache::key("b", &props("ca"), "Enchant")
+        );
+    }
+
+    #[test]
+    fn test_memory_eviction() {
+        let cache = Cache::new(2);
+        cache.insert("a", &[annotation("Enchant", 0, 1)]);
+        cache.insert("b", &[]);
+        assert!(cache.get("a").is_some());
+        cache.insert("c", &[]);
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("c").is_some());
+    }
+
+    #[test]
+    fn test_disk() {
+        let directory = tempfile::tempdir().unwrap();
+        let key = Cache::key("Tou", &props("en"), "Enchant");
+        Cache::with_directory(10, directory.path().to_path_buf())
+            .insert(&key, &[annotation("Enchant", 0, 3)]);
+
+        let cache = Cache::with_directory(10, directory.path().to_path_buf());
+        let annotations = cache.get(&key).unwrap();
+        assert_eq!(annotations[0].length, 3);
+
+        cache.clear().unwrap();
+        assert!(cache.get(&key).is_none());
+
+        // Keys of any shape are stored within the directory.
+        let inner = directory.path().join("inner");
+        let cache = Cache::with_directory(0, inner.clone());
+        for key in &["", "é", "../escape", "/absolute"] {
+            cache.insert(key, &[annotation("Enchant", 0, 3)]);
+            assert!(cache.get(key).is_some());
+        }
+        assert_eq!(fs::read_dir(directory.path()).unwrap().count(), 1);
+        assert!(!Path::new("/absolute.json").exists());
+    }
+
+    #[test]
+    fn test_disk_limit() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut cache = Cache::with_directory(0, directory.path().to_path_buf());
+        cache.set_disk_limit(2000);
+        let keys: Vec<String> = (0..30)
+            .map(|i| Cache::key(&i.to_string(), &props("en"), "Enchant"))
+            .collect();
+        for key in &keys {
+            cache.insert(key, &[annotation("Enchant", 0, 3)]);
+        }
+        assert!(evict(directory.path(), u64::MAX) <= 2000);
+        assert!(cache.get(&keys[29]).is_some());
+        assert!(cache.get(&keys[0]).is_none());
+    }
+
+    #[test]
+    fn test_paragraphs() {
+        let mut checker = tests::provider(0, &["teh"], AnnotationKind::Spelling);
+        // Keeps the dictionary out of the cache keys.
+        checker.extensions.add_word = None;
+        let mut patronus = tests::patronus(vec![checker]);
+        patronus.cache = Some(Cache::new(10));
+        let identity = patronus.providers[0].identity();
+        assert_eq!(
+            tests::check(&patronus, "A teh\n\nB teh"),
+            vec![(2, "First".to_string()), (9, "First".to_string())]
+        );
+
+        let cache = patronus.cache.as_ref().unwrap();
+        let annotations = cache.get(&Cache::key("B teh", &props("en"), &identity));
+        assert_eq!(annotations.unwrap()[0].offset, 2);
+        assert_eq!(
+            tests::check(&patronus, "B teh\n\nA teh"),
+            vec![(2, "First".to_string()), (9, "First".to_string())]
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use provider::segment;
+use std::ops::Range;
+
+/// Positions after which a chunk can end, from the most preferred kind.
+struct Boundaries {
+    kinds: [Vec<usize>; 3],
+}
+
+impl Boundaries {
+    fn new(text: &str) -> Self {
+        let ends = |segments: Vec<Range<usize>>| -> Vec<usize> {
+            segments.into_iter().map(|segment| segment.end).collect()
+        };
+        Self {
+            kinds: [
+                ends(segment::paragraphs(text)),
+                ends(segment::sentences(text)),
+                ends(segment::words(text)),
+            ],
+        }
+    }
+
+    /// Finds the last boundary in `(start, end]` of the most preferred kind.
+    fn last(&self, start: usize, end: usize) -> Option<usize> {
+        self.kinds.iter().find_map(|ends| {
+            // Ends are sorted so we can look up the last one not exceeding `end`.
+            let index = ends.partition_point(|&position| position <= end);
+            ends[..index]
+                .last()
+                .cloned()
+                .filter(|&position| position > start)
+        })
+    }
+}
+
+/// Splits text into chunks of at most `max_length` bytes so that it can be checked piecewise.
+///
+/// Chunks end at paragraph or sentence boundaries whenever possible, so that issues
+/// spanning several words, which providers only report within a sentence, are not cut in half.
+/// Only when a single sentence does not fit, it is split between words or, as the last resort,
+/// between characters.
+pub fn chunks(text: &str, max_length: usize) -> Vec<Range<usize>> {
+    let max_length = max_length.max(1);
+    let boundaries = Boundaries::new(text);
+    let mut result = Vec::new();
+    let mut start = 0;
+    while text.len() - start > max_length {
+        let mut end = start + max_length;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        let end = boundaries.last(start, end).unwrap_or(if end > start {
+            end
+        } else {
+            // Character longer than the limit.
+            start + text[start..].chars().next().map_or(0, char::len_utf8)
+        });
+        result.push(start..end);
+        start = end;
+    }
+    if start < text.len() || result.is_empty() {
+        result.push(start..text.len());
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(text: &str, max_length: usize) -> Vec<&str> {
+        chunks(text, max_length)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect()
+    }
+
+    #[test]
+    fn test_chunks() {
+        assert_eq!(split("Short.", 100), vec!["Short."]);
+        assert_eq!(split("", 100), vec![""]);
+        assert_eq!(
+            split("One. Two? Three!\n\nFour five.", 20),
+            vec!["One. Two? Three!", "\n\nFour five."]
+        );
+        assert_eq!(
+            split("One. Two? Three! Four five.", 20),
+            vec!["One. Two? Three!", " Four five."]
+        );
+        assert_eq!(
+            split("Very long sentence without end", 12),
+            vec!["Very long", " sentence", " without end"]
+        );
+        assert_eq!(split("žžž", 3), vec!["ž", "ž", "ž"]);
+        assert_eq!(split("žžž", 1), vec!["ž", "ž", "ž"]);
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::{HashMap, HashSet};
+use {Annotation, AnnotationKind};
+
+/// Agreement between providers required for reporting an annotation.
+#[derive(Clone, Debug)]
+pub enum Quorum {
+    /// At least given number of distinct providers.
+    Providers(usize),
+    /// Weights of the agreeing providers have to add up to at least `threshold`.
+    /// Providers missing from `weights` have weight 1.
+    Weighted {
+        weights: HashMap<String, f32>,
+        threshold: f32,
+    },
+}
+
+impl Quorum {
+    fn is_reached(&self, providers: &HashSet<&str>) -> bool {
+        match *self {
+            Quorum::Providers(count) => providers.len() >= count,
+            Quorum::Weighted {
+                ref weights,
+                threshold,
+            } => {
+                let total: f32 = providers
+                    .iter()
+                    .map(|provider| weights.get(*provider).cloned().unwrap_or(1.0))
+                    .sum();
+                total >= threshold
+            }
+        }
+    }
+}
+
+fn overlaps(a: &Annotation, b: &Annotation) -> bool {
+    (a.offset == b.offset && a.length == b.length)
+        || (a.offset < b.offset + b.length && b.offset < a.offset + a.length)
+}
+
+/// Drops annotations of kinds with a quorum that not enough providers agree on.
+///
+/// Providers agree when they report an annotation of the same kind on an overlapping range,
+/// either separately or merged into a single annotation.
+pub fn apply_consensus(
+    annotations: Vec<Annotation>,
+    quorums: &HashMap<AnnotationKind, Quorum>,
+) -> Vec<Annotation> {
+    if quorums.is_empty() {
+        return annotations;
+    }
+
+    let keep: Vec<bool> = annotations
+        .iter()
+        .map(|ann| match quorums.get(&ann.kind) {
+            Some(quorum) => {
+                let agreeing: HashSet<&str> = annotations
+                    .iter()
+                    .filter(|other| other.kind == ann.kind && overlaps(ann, other))
+                    .flat_map(|other| other.contributors.iter().map(String::as_str))
+                    .collect();
+                quorum.is_reached(&agreeing)
+            }
+            None => true,
+        })
+        .collect();
+
+    annotations
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(ann, keep)| if keep { Some(ann) } else { None })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tests::{check, patronus, provider};
+    use MergeOptions;
+
+    fn quorums(quorum: Quorum) -> HashMap<AnnotationKind, Quorum> {
+        let mut quorums = HashMap::new();
+        quorums.insert(AnnotationKind::Spelling, quorum);
+        quorums
+    }
+
+    #[test]
+    fn test_provider_count() {
+        let mut patronus = patronus(vec![
+            provider(0, &["Thsi", "teh", "tex"], AnnotationKind::Spelling),
+            provider(1, &["teh", "text"], AnnotationKind::Spelling),
+        ]);
+        patronus.consensus = quorums(Quorum::Providers(2));
+        let text = "Thsi is teh text.";
+        assert_eq!(
+            check(&patronus, text),
+            vec![
+                (8, "First".to_string()),
+                (12, "First".to_string()),
+                (8, "Second".to_string()),
+                (12, "Second".to_string())
+            ]
+        );
+
+        // Agreement is counted from contributors of merged annotations too.
+        patronus.merge = Some(MergeOptions::default());
+        assert_eq!(check(&patronus, text).len(), 3);
+
+        // Annotations of other kinds neither agree nor need to.
+        patronus.providers[1] = provider(1, &["Thsi"], AnnotationKind::Grammar);
+        assert_eq!(check(&patronus, text), vec![(0, "Second".to_string())]);
+    }
+
+    #[test]
+    fn test_weighted() {
+        let mut weights = HashMap::new();
+        weights.insert("Second".to_string(), 2.0);
+        let mut patronus = patronus(vec![
+            provider(0, &["Thsi"], AnnotationKind::Spelling),
+            provider(1, &["teh"], AnnotationKind::Spelling),
+        ]);
+        patronus.consensus = quorums(Quorum::Weighted {
+            weights,
+            threshold: 2.0,
+        });
+        assert_eq!(
+            check(&patronus, "Thsi is teh text."),
+            vec![(8, "Second".to_string())]
+        );
+    }
+}
@@ -0,0 +1,369 @@
+use error::Error;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use {Annotation, AnnotationKind, Properties};
+
+/// Directory with project configuration, looked up in the checked path and its ancestors.
+const PROJECT_DIRECTORY: &str = ".patronus";
+
+/// Name of the personal word list in `patronus` XDG data directory.
+const XDG_FILE: &str = "dictionary.txt";
+
+/// Path of the personal word list, if the XDG data directory is known.
+pub fn xdg_path() -> Option<PathBuf> {
+    xdg::BaseDirectories::with_prefix("patronus")
+        .ok()
+        .map(|xdg_dirs| xdg_dirs.get_data_home().join(XDG_FILE))
+}
+
+/// Words the user considers correct.
+///
+/// Personal words are stored in a file with one word per line,
+/// words ignored for the session are only kept in memory.
+/// Additionally, read-only word lists of a project can be loaded.
+#[derive(Clone, Debug, Default)]
+pub struct Dictionary {
+    words: BTreeSet<String>,
+    session: HashSet<String>,
+    /// Project words for all languages.
+    project: BTreeSet<String>,
+    /// Project words for specific languages, keyed by language tag.
+    project_languages: HashMap<String, BTreeSet<String>>,
+    /// File the personal words are saved to, `None` keeps them in memory only.
+    path: Option<PathBuf>,
+}
+
+impl Dictionary {
+    /// Creates an empty dictionary that is not saved anywhere.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens a word list file, which does not need to exist yet.
+    pub fn open(path: PathBuf) -> Result<Self, Error> {
+        let words = match fs::read_to_string(&path) {
+            Ok(contents) => parse_words(&contents),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => BTreeSet::new(),
+            Err(source) => return Err(Error::IoError { source }),
+        };
+        Ok(Self {
+            words,
+            path: Some(path),
+            ..Self::default()
+        })
+    }
+
+    /// Opens the `dictionary.txt` word list in `patronus` XDG data directory.
+    pub fn open_xdg() -> Result<Self, Error> {
+        let xdg_dirs =
+            xdg::BaseDirectories::with_prefix("patronus").map_err(|err| Error::ConfigError {
+                message: err.to_string(),
+            })?;
+        Self::open(xdg_dirs.get_data_home().join(XDG_FILE))
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Adds a word to the personal word list.
+    /// Returns whether the word was not present.
+    pub fn add(&mut self, word: &str) -> Result<bool, Error> {
+        if !self.words.insert(word.to_string()) {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Removes a word from the personal word list and the session.
+    /// Returns whether the word was present in either of them.
+    pub fn remove(&mut self, word: &str) -> Result<bool, Error> {
+        let in_session = self.session.remove(word);
+        if !self.words.remove(word) {
+            return Ok(in_session);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Accepts a word until the dictionary is dropped, without saving it.
+    pub fn ignore_for_session(&mut self, word: &str) {
+        self.session.insert(word.to_string());
+    }
+
+    /// Loads project word lists from `.patronus` directories in `path` and its ancestors,
+    /// replacing previously loaded ones.
+    ///
+    /// `dictionary.txt` applies to all languages, `dictionary.<lang>.txt` only to texts
+    /// whose language is `<lang>` or its variant, e.g. `dictionary.en.txt` applies to `en-GB`.
+    /// Returns the files that were read.
+    pub fn load_project(&mut self, path: &Path) -> Result<Vec<PathBuf>, Error> {
+        self.project.clear();
+        self.project_languages.clear();
+        let mut loaded = Vec::new();
+        for directory in path.ancestors() {
+            let directory = directory.join(PROJECT_DIRECTORY);
+            if !directory.is_dir() {
+                continue;
+            }
+            let entries = fs::read_dir(&directory).map_err(|source| Error::IoError { source })?;
+            for entry in entries {
+                let path = entry.map_err(|source| Error::IoError { source })?.path();
+                let name = match path.file_name().and_then(|name| name.to_str()) {
+                    Some(name) => name,
+                    None => continue,
+                };
+                let words = if name == "dictionary.txt" {
+                    &mut self.project
+                } else if let Some(language) = name
+                    .strip_prefix("dictionary.")
+                    .and_then(|rest| rest.strip_suffix(".txt"))
+                {
+                    self.project_languages
+                        .entry(language.to_string())
+                        .or_default()
+                } else {
+                    continue;
+                };
+                let contents =
+                    fs::read_to_string(&path).map_err(|source| Error::IoError { source })?;
+                words.extend(parse_words(&contents));
+                loaded.push(path);
+            }
+        }
+        loaded.sort();
+        Ok(loaded)
+    }
+
+    /// Words in the personal word list.
+    pub fn words(&self) -> impl Iterator<Item = &str> {
+        self.words.iter().map(String::as_str)
+    }
+
+    /// Words accepted until the dictionary is dropped.
+    pub fn session_words(&self) -> impl Iterator<Item = &str> {
+        self.session.iter().map(String::as_str)
+    }
+
+    /// Checks whether a word is known in given language.
+    /// Capitalized words, e.g. at the start of a sentence, also match their lowercase entries.
+    pub fn contains(&self, word: &str, language: &str) -> bool {
+        let known = |word: &str| {
+            self.words.contains(word)
+                || self.session.contains(word)
+                || self.project.contains(word)
+                || self
+                    .project_languages
+                    .iter()
+                    .any(|(tag, words)| matches_language(tag, language) && words.contains(word))
+        };
+        if known(word) {
+            return true;
+        }
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) if first.is_uppercase() => {
+                known(&(first.to_lowercase().collect::<String>() + chars.as_str()))
+            }
+            _ => false,
+        }
+    }
+
+    /// String that changes whenever the accepted words change.
+    pub fn fingerprint(&self) -> String {
+        let mut session: Vec<&str> = self.session.iter().map(String::as_str).collect();
+        session.sort_unstable();
+        let words: Vec<&str> = self.words().collect();
+        let project: Vec<&str> = self.project.iter().map(String::as_str).collect();
+        let mut languages: Vec<String> = self
+            .project_languages
+            .iter()
+            .map(|(tag, words)| {
+                let words: Vec<&str> = words.iter().map(String::as_str).collect();
+                format!("{}:{}", tag, words.join("\n"))
+            })
+            .collect();
+        languages.sort_unstable();
+        format!(
+            "{}\0{}\0{}\0{}",
+            words.join("\n"),
+            session.join("\n"),
+            project.join("\n"),
+            languages.join("\0")
+        )
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        let write = || -> io::Result<()> {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let contents: String = self.words.iter().map(|word| word.clone() + "\n").collect();
+            let temporary = path.with_extension("tmp");
+            fs::write(&temporary, contents)?;
+            fs::rename(&temporary, path)
+        };
+        write().map_err(|source| Error::IoError { source })
+    }
+}
+
+/// Checks whether a language tag like `en` covers a language like `en-GB` or `en_GB`.
+pub(crate) fn matches_language(tag: &str, language: &str) -> bool {
+    language.eq_ignore_ascii_case(tag)
+        || language.get(..tag.len()).is_some_and(|prefix| {
+            prefix.eq_ignore_ascii_case(tag) && language[tag.len()..].starts_with(['-', '_'])
+        })
+}
+
+/// Parses a word list with one word per line, skipping blank lines and `#` comments.
+pub(crate) fn parse_words(contents: &str) -> BTreeSet<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Discards spelling annotations of words known to the dictionary.
+pub fn filter_known(
+    annotations: Vec<Annotation>,
+    text: &str,
+    props: &Properties,
+    dictionary: &Dictionary,
+) -> Vec<Annotation> {
+    annotations
+        .into_iter()
+        .filter(|ann| {
+            ann.kind != AnnotationKind::Spelling
+                || !text
+                    .get(ann.offset..ann.offset + ann.length)
+                    .is_some_and(|word| dictionary.contains(word, &props.primary_language))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tests;
+
+    #[test]
+    fn test_contains() {
+        let mut dictionary = Dictionary::new();
+        dictionary.add("patronus").unwrap();
+        dictionary.ignore_for_session("Enchant");
+        assert!(dictionary.contains("patronus", "en"));
+        assert!(dictionary.contains("Patronus", "en"));
+        assert!(dictionary.contains("Enchant", "en"));
+        assert!(!dictionary.contains("enchant", "en"));
+        assert!(!dictionary.contains("PATRONUS", "en"));
+
+        dictionary.remove("Enchant").unwrap();
+        assert!(!dictionary.contains("Enchant", "en"));
+    }
+
+    #[test]
+    fn test_file() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("patronus").join("dictionary.txt");
+        let mut dictionary = Dictionary::open(path.clone()).unwrap();
+        assert!(dictionary.add("zlutoucky").unwrap());
+        assert!(dictionary.add("kun").unwrap());
+        assert!(!dictionary.add("kun").unwrap());
+        dictionary.ignore_for_session("upel");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "kun\nzlutoucky\n");
+
+        let mut dictionary = Dictionary::open(path.clone()).unwrap();
+        assert!(!dictionary.contains("upel", "en"));
+        assert!(dictionary.remove("kun").unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "zlutoucky\n");
+    }
+
+    #[test]
+    fn test_project() {
+        let root = tempfile::tempdir().unwrap();
+        let nested = root.path().join("crates").join("patronus");
+        fs::create_dir_all(root.path().join(".patronus")).unwrap();
+        fs::create_dir_all(nested.join(".patronus")).unwrap();
+        fs::write(
+            root.path().join(".patronus/dictionary.txt"),
+            "# Jargon\nrustc\n",
+        )
+        .unwrap();
+        fs::write(nested.join(".patronus/dictionary.en.txt"), "colour\n").unwrap();
+        fs::write(nested.join(".patronus/other.txt"), "other\n").unwrap();
+
+        let mut dictionary = Dictionary::new();
+        let loaded = dictionary.load_project(&nested.join("src")).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert!(dictionary.contains("rustc", "cs"));
+        assert!(dictionary.contains("colour", "en-GB"));
+        assert!(dictionary.contains("colour", "en"));
+        assert!(!dictionary.contains("colour", "eo"));
+        assert!(!dictionary.contains("other", "en"));
+        assert!(!dictionary.contains("Jargon", "en"));
+
+        let fingerprint = dictionary.fingerprint();
+        dictionary.load_project(root.path()).unwrap();
+        assert!(dictionary.contains("rustc", "en"));
+        assert!(!dictionary.contains("colour", "en"));
+        assert_ne!(dictionary.fingerprint(), fingerprint);
+
+        let mut patronus = tests::patronus(vec![tests::provider(0, &[], AnnotationKind::Spelling)]);
+        let generation = patronus.generation();
+        patronus.load_project_dictionary(&nested).unwrap();
+        assert!(patronus.dictionary.contains("colour", "en"));
+        assert_ne!(patronus.generation(), generation);
+    }
+
+    #[test]
+    fn test_teach_providers() {
+        let mut patronus = tests::patronus(vec![tests::provider(0, &[], AnnotationKind::Spelling)]);
+        patronus.add_word("kun").unwrap();
+        patronus.add_word("kun").unwrap();
+        patronus.ignore_word_for_session("upel");
+        // Words that were not accepted are not forwarded.
+        patronus.remove_word("dabel").unwrap();
+        patronus.remove_word("upel").unwrap();
+        assert_eq!(
+            tests::taught(&patronus.providers[0]),
+            vec!["+kun", "~upel", "-upel"]
+        );
+
+        let mut dictionary = Dictionary::new();
+        dictionary.add("zlutoucky").unwrap();
+        dictionary.add("kun").unwrap();
+        dictionary.ignore_for_session("pel");
+        patronus.set_dictionary(dictionary);
+        let mut taught = tests::taught(&patronus.providers[0]);
+        taught.sort();
+        assert_eq!(taught, vec!["+kun", "+zlutoucky", "~pel"]);
+
+        patronus.set_dictionary(Dictionary::new());
+        let mut taught = tests::taught(&patronus.providers[0]);
+        taught.sort();
+        assert_eq!(taught, vec!["-kun", "-pel", "-zlutoucky"]);
+    }
+
+    #[test]
+    fn test_filter_known() {
+        let mut patronus = tests::patronus(vec![
+            tests::provider(0, &["Jan", "Tojnar"], AnnotationKind::Spelling),
+            tests::provider(1, &["Tojnar"], AnnotationKind::Grammar),
+        ]);
+        patronus.ignore_word_for_session("Tojnar");
+        // Only misspellings of known words are hidden.
+        assert_eq!(
+            tests::check(&patronus, "Jan Tojnar"),
+            vec![(0, "First".to_string()), (4, "Second".to_string())]
+        );
+    }
+}
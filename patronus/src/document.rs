@@ -0,0 +1,163 @@
+use provider::segment::paragraphs;
+use std::collections::HashMap;
+use std::ops::Range;
+use {Annotation, Patronus, Properties, Suppressions};
+
+/// Paragraph of a document with annotations relative to its start.
+struct Paragraph {
+    range: Range<usize>,
+    /// `None` until the paragraph is checked.
+    annotations: Option<Vec<Annotation>>,
+}
+
+/// Text kept between checks so that only paragraphs changed since the last check
+/// need to be sent to the providers.
+pub struct Document {
+    text: String,
+    paragraphs: Vec<Paragraph>,
+    /// Properties and generation of the checker the paragraphs were checked with.
+    checked_with: Option<(Properties, u64)>,
+}
+
+impl Document {
+    pub fn new(text: &str) -> Self {
+        let mut document = Self {
+            text: String::new(),
+            paragraphs: Vec::new(),
+            checked_with: None,
+        };
+        document.update(text);
+        document
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Replaces the text of the document.
+    /// Paragraphs whose text did not change keep their annotations.
+    pub fn update(&mut self, text: &str) {
+        let mut checked: HashMap<&str, Vec<Vec<Annotation>>> = HashMap::new();
+        for paragraph in self.paragraphs.drain(..).rev() {
+            if let Some(annotations) = paragraph.annotations {
+                checked
+                    .entry(&self.text[paragraph.range])
+                    .or_default()
+                    .push(annotations);
+            }
+        }
+
+        let paragraphs = paragraphs(text)
+            .into_iter()
+            .map(|range| Paragraph {
+                annotations: checked
+                    .get_mut(&text[range.clone()])
+                    .and_then(|annotations| annotations.pop()),
+                range,
+            })
+            .collect();
+
+        self.paragraphs = paragraphs;
+        self.text = text.to_string();
+    }
+
+    /// Checks the paragraphs that changed since the last check
+    /// and returns annotations for the whole document.
+    /// All paragraphs are checked again when the properties or the checker’s configuration changed.
+    pub fn check(&mut self, patronus: &Patronus, props: &Properties) -> Vec<Annotation> {
+        let checked_with = (props.clone(), patronus.generation());
+        if self.checked_with.as_ref() != Some(&checked_with) {
+            for paragraph in &mut self.paragraphs {
+                paragraph.annotations = None;
+            }
+            self.checked_with = Some(checked_with);
+        }
+
+        for paragraph in &mut self.paragraphs {
+            if paragraph.annotations.is_none() {
+                let text = &self.text[paragraph.range.clone()];
+                paragraph.annotations = Some(patronus.check_str(props, text));
+            }
+        }
+
+        // Directives can span several paragraphs.
+        if patronus.suppressions {
+            Suppressions::parse(&self.text).apply(self.annotations(), &self.text)
+        } else {
+            self.annotations()
+        }
+    }
+
+    /// Returns annotations of the paragraphs checked so far, with offsets into the whole document.
+    pub fn annotations(&self) -> Vec<Annotation> {
+        self.paragraphs
+            .iter()
+            .flat_map(|paragraph| {
+                paragraph
+                    .annotations
+                    .iter()
+                    .flatten()
+                    .cloned()
+                    .map(move |mut ann| {
+                        ann.shift(paragraph.range.start);
+                        ann
+                    })
+            })
+            .collect()
+    }
+
+    /// Number of paragraphs that need to be checked.
+    pub fn unchecked_paragraphs(&self) -> usize {
+        self.paragraphs
+            .iter()
+            .filter(|paragraph| paragraph.annotations.is_none())
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tests::{self, annotation};
+    use AnnotationKind;
+
+    #[test]
+    fn test_update_keeps_unchanged_paragraphs() {
+        let mut document = Document::new("Tou\n\nmanny\n\nmisteaks");
+        for paragraph in &mut document.paragraphs {
+            let length = paragraph.range.len();
+            paragraph.annotations = Some(vec![annotation("Enchant", 0, length)]);
+        }
+
+        document.update("Too\n\nmanny\n\nmisteaks\n\nmisteaks");
+        assert_eq!(document.unchecked_paragraphs(), 2);
+        let checked: Vec<(usize, usize)> = document
+            .annotations()
+            .iter()
+            .map(|ann| (ann.offset, ann.length))
+            .collect();
+        assert_eq!(checked, vec![(5, 5), (12, 8)]);
+    }
+
+    #[test]
+    fn test_configuration_change() {
+        let mut patronus = tests::patronus(vec![tests::provider(
+            0,
+            &["manny", "misteaks"],
+            AnnotationKind::Spelling,
+        )]);
+        let props = Properties {
+            primary_language: "en".to_string(),
+        };
+        let mut document = Document::new("Tou\n\nmanny\n\nmisteaks");
+        assert_eq!(document.check(&patronus, &props).len(), 2);
+
+        patronus.add_word("manny").unwrap();
+        let checked: Vec<usize> = document
+            .check(&patronus, &props)
+            .iter()
+            .map(|ann| ann.offset)
+            .collect();
+        assert_eq!(checked, vec![12]);
+    }
+}
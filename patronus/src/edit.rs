@@ -0,0 +1,275 @@
+use error::Error;
+use std::ops::Range;
+use {Annotation, Suggestion};
+
+/// Replacement of a byte range of a text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    /// Text to put in place of the range, empty for deletion.
+    pub replacement: String,
+}
+
+impl TextEdit {
+    pub fn new(range: Range<usize>, replacement: &str) -> Self {
+        Self {
+            range,
+            replacement: replacement.to_string(),
+        }
+    }
+
+    /// Creates an edit applying a suggestion of given annotation.
+    pub fn from_suggestion(annotation: &Annotation, suggestion: &Suggestion) -> Self {
+        Self {
+            range: suggestion.range(annotation),
+            replacement: suggestion.replacement.clone(),
+        }
+    }
+}
+
+impl Annotation {
+    /// Returns the suggestion the provider considers the best –
+    /// the most confident one, or the first one when the confidence is not known.
+    pub fn top_suggestion(&self) -> Option<&Suggestion> {
+        self.suggestions
+            .iter()
+            .fold(None, |best, suggestion| match best {
+                Some(best) if best.confidence >= suggestion.confidence => Some(best),
+                _ => Some(suggestion),
+            })
+    }
+}
+
+fn overlapping(a: &Range<usize>, b: &Range<usize>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+/// Applies edits to a text.
+///
+/// All ranges refer to the original text so the edits can be passed in any order.
+/// Insertions at the same position are applied in the order they were passed.
+/// Fails when the edits overlap or a range does not lie on character boundaries of the text.
+pub fn apply(text: &str, edits: &[TextEdit]) -> Result<String, Error> {
+    let mut sorted: Vec<&TextEdit> = edits.iter().collect();
+    sorted.sort_by_key(|edit| (edit.range.start, edit.range.end));
+
+    for edit in &sorted {
+        let range = &edit.range;
+        if range.start > range.end
+            || !text.is_char_boundary(range.start)
+            || !text.is_char_boundary(range.end)
+        {
+            return Err(Error::InvalidRange {
+                range: range.clone(),
+            });
+        }
+    }
+    for pair in sorted.windows(2) {
+        if overlapping(&pair[0].range, &pair[1].range) {
+            return Err(Error::OverlappingEdits {
+                first: pair[0].range.clone(),
+                second: pair[1].range.clone(),
+            });
+        }
+    }
+
+    let mut result = String::with_capacity(text.len());
+    let mut position = 0;
+    for edit in sorted {
+        result.push_str(&text[position..edit.range.start]);
+        result.push_str(&edit.replacement);
+        position = edit.range.end;
+    }
+    result.push_str(&text[position..]);
+    Ok(result)
+}
+
+/// Applies the top suggestion of every annotation the provider is at least `min_confidence` sure about.
+///
+/// Annotations are fixed from the most confident one, fixes overlapping an already chosen one are skipped.
+/// Annotations of unknown confidence are never fixed; this includes all annotations of the Enchant
+/// and LanguageTool providers since neither checker reports how sure it is.
+pub fn auto_fix(
+    text: &str,
+    annotations: &[Annotation],
+    min_confidence: f32,
+) -> Result<String, Error> {
+    let mut candidates: Vec<(f32, TextEdit)> = annotations
+        .iter()
+        .filter_map(|ann| match ann.confidence {
+            Some(confidence) if confidence >= min_confidence => ann
+                .top_suggestion()
+                .map(|suggestion| (confidence, TextEdit::from_suggestion(ann, suggestion))),
+            _ => None,
+        })
+        .collect();
+    candidates.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+    let mut edits: Vec<TextEdit> = Vec::with_capacity(candidates.len());
+    for (_, edit) in candidates {
+        if !edits
+            .iter()
+            .any(|chosen| chosen.range == edit.range || overlapping(&chosen.range, &edit.range))
+        {
+            edits.push(edit);
+        }
+    }
+
+    apply(text, &edits)
+}
+
+/// Where a position ends up after a range is replaced, `None` when it was inside the range.
+fn map_position(position: usize, edit: &TextEdit) -> Option<usize> {
+    if position <= edit.range.start {
+        Some(position)
+    } else if position >= edit.range.end {
+        Some(position - edit.range.len() + edit.replacement.len())
+    } else {
+        None
+    }
+}
+
+/// Updates annotation for an edit, returns `false` when the annotation is no longer valid.
+fn rebase_annotation(ann: &mut Annotation, edit: &TextEdit) -> bool {
+    let start = ann.offset;
+    let end = ann.offset + ann.length;
+    let range = &edit.range;
+
+    if range.end <= start {
+        // The edit precedes the annotated text.
+        ann.offset = start - range.len() + edit.replacement.len();
+    } else if range.start >= end {
+        // The edit follows the annotated text.
+    } else if edit.replacement.is_empty() && (range.start <= start || range.end >= end) {
+        // Deletion cut off a part of the annotated text.
+        let new_start = map_position(start, edit).unwrap_or(range.start);
+        let new_end = map_position(end, edit).unwrap_or(range.start);
+        if new_end <= new_start {
+            return false;
+        }
+        ann.offset = new_start;
+        ann.length = new_end - new_start;
+        // Suggestions were meant for the whole text.
+        ann.suggestions.clear();
+        return true;
+    } else {
+        // The annotated text was rewritten.
+        return false;
+    }
+
+    ann.suggestions
+        .retain_mut(|suggestion| match suggestion.range {
+            Some(ref mut range) => {
+                if edit.range.end <= range.start {
+                    *range = range.start - edit.range.len() + edit.replacement.len()
+                        ..range.end - edit.range.len() + edit.replacement.len();
+                    true
+                } else {
+                    edit.range.start >= range.end
+                }
+            }
+            None => true,
+        });
+    true
+}
+
+/// Moves annotations of a text to the positions they have after the text was edited.
+///
+/// The edits are applied in sequence, as they come from an editor, so the range of every edit
+/// refers to the text resulting from the previous ones. Annotations are shifted when text is
+/// changed before them, shrunk when a deletion removes part of them and dropped when the text
+/// they annotate is changed otherwise.
+pub fn rebase(annotations: Vec<Annotation>, edits: &[TextEdit]) -> Vec<Annotation> {
+    annotations
+        .into_iter()
+        .filter_map(|mut ann| {
+            if edits.iter().all(|edit| rebase_annotation(&mut ann, edit)) {
+                Some(ann)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tests::annotation;
+
+    #[test]
+    fn test_apply() {
+        let text = "Tou manny misteaks";
+        let edits = [
+            TextEdit::new(10..18, "mistakes"),
+            TextEdit::new(0..3, "Too"),
+            TextEdit::new(4..9, "many"),
+            TextEdit::new(18..18, "!"),
+        ];
+        assert_eq!(apply(text, &edits).unwrap(), "Too many mistakes!");
+    }
+
+    #[test]
+    fn test_apply_rejects_overlapping() {
+        let edits = [TextEdit::new(0..3, "a"), TextEdit::new(2..5, "b")];
+        assert!(matches!(
+            apply("abcdef", &edits),
+            Err(Error::OverlappingEdits { .. })
+        ));
+        assert!(matches!(
+            apply("žluť", &[TextEdit::new(1..2, "z")]),
+            Err(Error::InvalidRange { .. })
+        ));
+    }
+
+    #[test]
+    fn test_rebase() {
+        // “Tou manny misteaks”
+        let anns = vec![
+            annotation("Enchant", 0, 3),
+            annotation("Enchant", 4, 5),
+            annotation("Enchant", 10, 8),
+        ];
+        let edits = [
+            // “Tou many misteaks”
+            TextEdit::new(6..7, ""),
+            // “Well, Tou many misteaks”
+            TextEdit::new(0..0, "Well, "),
+            // “Wellou many misteaks”
+            TextEdit::new(5..7, ""),
+        ];
+        let rebased: Vec<(usize, usize)> = rebase(anns, &edits)
+            .iter()
+            .map(|ann| (ann.offset, ann.length))
+            .collect();
+        assert_eq!(rebased, vec![(5, 2), (13, 8)]);
+    }
+
+    #[test]
+    fn test_auto_fix() {
+        let suggestion = |replacement: &str, confidence| Suggestion {
+            replacement: replacement.to_string(),
+            description: None,
+            confidence,
+            range: None,
+        };
+        let mut sure = annotation("Enchant", 0, 3);
+        sure.confidence = Some(0.9);
+        sure.suggestions = vec![suggestion("Two", Some(0.2)), suggestion("Too", Some(0.8))];
+        let mut unsure = annotation("Enchant", 4, 5);
+        unsure.confidence = Some(0.3);
+        unsure.suggestions = vec![suggestion("many", None)];
+        let mut conflicting = annotation("LanguageTool", 0, 9);
+        conflicting.confidence = Some(0.6);
+        conflicting.suggestions = vec![suggestion("So many", None)];
+
+        let mut invalid = annotation("Enchant", 4, 5);
+        invalid.confidence = Some(f32::NAN);
+        invalid.suggestions = vec![suggestion("may", None)];
+
+        assert_eq!(
+            auto_fix("Tou manny", &[unsure, conflicting, sure, invalid], 0.5).unwrap(),
+            "Too manny"
+        );
+    }
+}
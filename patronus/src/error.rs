@@ -1,4 +1,5 @@
 use std::io;
+use std::ops::Range;
 
 /// Errors.
 #[derive(Debug)]
@@ -14,6 +15,28 @@ pub enum Error {
         /// The source error.
         source: libloading::Error,
     },
+    /// Configuration file is invalid.
+    ConfigError {
+        /// Description of the problem.
+        message: String,
+    },
+    /// Edit range is out of bounds or not on character boundaries.
+    InvalidRange {
+        /// The offending range.
+        range: Range<usize>,
+    },
+    /// Edits cannot be applied together.
+    OverlappingEdits {
+        /// Range of the earlier edit.
+        first: Range<usize>,
+        /// Range of the edit overlapping it.
+        second: Range<usize>,
+    },
+    /// Regular expression is invalid.
+    InvalidPattern {
+        /// The source error.
+        source: regex::Error,
+    },
 }
 
 impl std::error::Error for Error {
@@ -22,6 +45,8 @@ impl std::error::Error for Error {
         match *self {
             IoError { ref source } => Some(source),
             LibloadingError { ref source } => Some(source),
+            InvalidPattern { ref source } => Some(source),
+            ConfigError { .. } | InvalidRange { .. } | OverlappingEdits { .. } => None,
         }
     }
 }
@@ -32,6 +57,13 @@ impl std::fmt::Display for Error {
         match *self {
             IoError { ref source } => write!(f, "IO failed: {}", source),
             LibloadingError { ref source } => write!(f, "Libloading failed: {}", source),
+            ConfigError { ref message } => write!(f, "Invalid configuration: {}", message),
+            InvalidRange { ref range } => write!(f, "Invalid range {:?}", range),
+            OverlappingEdits {
+                ref first,
+                ref second,
+            } => write!(f, "Edits at {:?} and {:?} overlap", first, second),
+            InvalidPattern { ref source } => write!(f, "Invalid pattern: {}", source),
         }
     }
 }
@@ -0,0 +1,478 @@
+//! Comments and string literals of source code.
+
+use super::{end_block, Fragment};
+use regex::Regex;
+use std::ops::Range;
+use std::sync::OnceLock;
+use SourceMap;
+
+/// Programming languages whose sources can be checked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Language {
+    Rust,
+    C,
+    Python,
+    JavaScript,
+    Shell,
+}
+
+impl Language {
+    /// Guesses the language from a file extension, e.g. `rs`.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "rs" => Some(Language::Rust),
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" => Some(Language::C),
+            "py" | "pyi" => Some(Language::Python),
+            "js" | "mjs" | "cjs" | "jsx" | "ts" | "tsx" => Some(Language::JavaScript),
+            "sh" | "bash" | "zsh" => Some(Language::Shell),
+            _ => None,
+        }
+    }
+
+    fn has_slash_comments(self) -> bool {
+        matches!(self, Language::Rust | Language::C | Language::JavaScript)
+    }
+}
+
+/// What to extract from source code besides comments, nothing by default.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CodeOptions {
+    /// Whether string literals are checked too, each as a separate block.
+    pub strings: bool,
+    /// Whether identifiers like `parseHtml` or `max_value` are split into words.
+    /// Capitalized words like `JavaScript` are left alone since they are usually names.
+    pub split_identifiers: bool,
+}
+
+/// Extracts prose from comments (and optionally string literals) of source code.
+///
+/// Comment markers, including the leading `*` of block comment lines, are stripped and
+/// consecutive line comments form one block. Python docstrings are treated as comments.
+pub fn extract(source: &str, language: Language, options: &CodeOptions) -> Vec<Fragment> {
+    let mut extractor = Extractor {
+        source,
+        language,
+        options,
+        map: SourceMap::new(),
+        lines: Vec::new(),
+        lines_end: 0,
+    };
+    extractor.run();
+    vec![Fragment {
+        map: extractor.map,
+        language: None,
+    }]
+}
+
+struct Extractor<'a> {
+    source: &'a str,
+    language: Language,
+    options: &'a CodeOptions,
+    map: SourceMap,
+    /// Contents of the line comments of the current block.
+    lines: Vec<Range<usize>>,
+    /// End of the last line comment.
+    lines_end: usize,
+}
+
+impl<'a> Extractor<'a> {
+    fn run(&mut self) {
+        let source = self.source;
+        let language = self.language;
+        let mut position = 0;
+        while position < source.len() {
+            let rest = &source[position..];
+            let c = rest.chars().next().expect("position is inside source");
+            if language.has_slash_comments() && rest.starts_with("//") {
+                let mut start = position + 2;
+                if language == Language::Rust
+                    && (rest[2..].starts_with('/') && !rest[2..].starts_with("//")
+                        || rest[2..].starts_with('!'))
+                {
+                    start += 1;
+                }
+                position = self.line_comment(start);
+            } else if language.has_slash_comments() && rest.starts_with("/*") {
+                position = self.block_comment(position);
+            } else if c == '#'
+                && matches!(language, Language::Python | Language::Shell)
+                && (language == Language::Python || starts_word(source, position))
+            {
+                if position == 0 && rest.starts_with("#!") {
+                    position = line_end(source, position);
+                } else {
+                    position = self.line_comment(position + 1);
+                }
+            } else if c == '"' || c == '\'' || c == '`' && language == Language::JavaScript {
+                position = self.quoted(position, c);
+            } else if c == '\\' && language == Language::Shell {
+                position += 1 + rest[1..].chars().next().map_or(0, char::len_utf8);
+            } else if c.is_alphanumeric() || c == '_' {
+                let end = rest
+                    .find(|c: char| !c.is_alphanumeric() && c != '_')
+                    .map_or(source.len(), |end| position + end);
+                let identifier = &source[position..end];
+                position = end;
+                if language == Language::Rust && (identifier == "r" || identifier == "br") {
+                    position = self.raw_string(end);
+                }
+            } else {
+                position += c.len_utf8();
+            }
+        }
+        self.flush_lines();
+    }
+
+    /// Collects a line comment whose text starts at `start` and returns the end of the line.
+    fn line_comment(&mut self, mut start: usize) -> usize {
+        let source = self.source;
+        let end = line_end(source, start);
+        if source[start..end].starts_with(' ') {
+            start += 1;
+        }
+        let comment_start = source[..start].rfind('\n').map_or(0, |newline| newline + 1);
+        // Comments separated by code or blank lines form separate blocks.
+        let between = &source[self.lines_end.min(comment_start)..comment_start];
+        if self.lines.is_empty()
+            || !between.trim().is_empty()
+            || between.matches('\n').count() > 1
+            || !source[comment_start..start]
+                .trim_start()
+                .starts_with(comment_marker(self.language))
+        {
+            self.flush_lines();
+        }
+        self.lines.push(start..end);
+        self.lines_end = end;
+        end
+    }
+
+    fn flush_lines(&mut self) {
+        let lines = std::mem::take(&mut self.lines);
+        self.push_lines(&lines);
+    }
+
+    /// Handles a block comment at `position` and returns the position after it.
+    fn block_comment(&mut self, position: usize) -> usize {
+        self.flush_lines();
+        let source = self.source;
+        let mut start = position + 2;
+        let mut depth = 1;
+        let mut index = start;
+        let mut end = source.len();
+        while index < source.len() {
+            let rest = &source[index..];
+            if rest.starts_with("*/") {
+                depth -= 1;
+                if depth == 0 || self.language != Language::Rust {
+                    end = index;
+                    break;
+                }
+                index += 2;
+            } else if rest.starts_with("/*") && self.language == Language::Rust {
+                depth += 1;
+                index += 2;
+            } else {
+                index += rest.chars().next().map_or(1, char::len_utf8);
+            }
+        }
+        if start < end && (source[start..].starts_with('*') || source[start..].starts_with('!')) {
+            start += 1;
+        }
+
+        let mut lines = Vec::new();
+        let mut line_start = start;
+        for line in source[start..end].split('\n') {
+            let indent = line.len() - line.trim_start().len();
+            let mut content = line_start + indent..line_start + line.len();
+            let text = &source[content.clone()];
+            if text.starts_with('*') {
+                content.start += if text.starts_with("* ") { 2 } else { 1 };
+            }
+            lines.push(content);
+            line_start += line.len() + 1;
+        }
+        self.push_lines(&lines);
+        (end + 2).min(source.len())
+    }
+
+    /// Handles a string literal opened by `quote` at `position` and returns the position after it.
+    fn quoted(&mut self, position: usize, quote: char) -> usize {
+        let source = self.source;
+        let rest = &source[position..];
+        if self.language == Language::Rust && quote == '\'' {
+            // Character literal or a lifetime.
+            let mut chars = rest.char_indices().skip(1);
+            return match (chars.next(), chars.next()) {
+                (Some((_, '\\')), _) => source[position + 2..]
+                    .find('\'')
+                    .map_or(source.len(), |end| position + 2 + end + 1),
+                (Some(_), Some((offset, '\''))) => position + offset + 1,
+                _ => position + 1,
+            };
+        }
+
+        let triple = self.language == Language::Python
+            && (rest.starts_with("\"\"\"") || rest.starts_with("'''"));
+        let delimiter = if triple { &rest[..3] } else { &rest[..1] };
+        let start = position + delimiter.len();
+        let escapes = !(self.language == Language::Shell && quote == '\'');
+        let mut index = start;
+        let end = loop {
+            let rest = &source[index..];
+            if rest.is_empty() {
+                break source.len();
+            }
+            if rest.starts_with(delimiter) {
+                break index;
+            }
+            let c = rest.chars().next().expect("rest is not empty");
+            index += c.len_utf8();
+            if c == '\\' && escapes {
+                index += source[index..].chars().next().map_or(0, char::len_utf8);
+            }
+        };
+
+        let line_start = source[..position]
+            .rfind('\n')
+            .map_or(0, |newline| newline + 1);
+        let docstring = triple && source[line_start..position].trim().is_empty();
+        if docstring {
+            self.flush_lines();
+            let mut lines = Vec::new();
+            let mut line_start = start;
+            for line in source[start..end].split('\n') {
+                let indent = line.len() - line.trim_start().len();
+                lines.push(line_start + indent..line_start + line.len());
+                line_start += line.len() + 1;
+            }
+            self.push_lines(&lines);
+        } else if self.options.strings {
+            self.flush_lines();
+            if escapes {
+                self.push_escaped(start..end);
+            } else {
+                self.push_prose(start..end);
+            }
+            end_block(&mut self.map, end);
+        }
+        (end + delimiter.len()).min(source.len())
+    }
+
+    /// Handles a Rust raw string following its prefix at `position`.
+    fn raw_string(&mut self, position: usize) -> usize {
+        let source = self.source;
+        let hashes = source[position..]
+            .find(|c| c != '#')
+            .unwrap_or(source.len() - position);
+        if !source[position + hashes..].starts_with('"') {
+            return position;
+        }
+        let start = position + hashes + 1;
+        let closing = format!("\"{}", "#".repeat(hashes));
+        let end = source[start..]
+            .find(&closing)
+            .map_or(source.len(), |end| start + end);
+        if self.options.strings {
+            self.flush_lines();
+            self.push_prose(start..end);
+            end_block(&mut self.map, end);
+        }
+        (end + closing.len()).min(source.len())
+    }
+
+    /// Appends lines of a comment as a block, blank lines separate paragraphs.
+    fn push_lines(&mut self, lines: &[Range<usize>]) {
+        let mut pushed = false;
+        let mut paragraph = false;
+        for line in lines {
+            let text = self.source[line.clone()].trim_end();
+            if text.is_empty() {
+                paragraph = pushed;
+                continue;
+            }
+            if paragraph {
+                end_block(&mut self.map, line.start);
+                paragraph = false;
+            } else if pushed {
+                self.map.push_inserted("\n", line.start);
+            }
+            self.push_prose(line.start..line.start + text.len());
+            pushed = true;
+        }
+        if let Some(last) = lines.last() {
+            end_block(&mut self.map, last.end);
+        }
+    }
+
+    /// Appends string literal contents, replacing escape sequences.
+    fn push_escaped(&mut self, range: Range<usize>) {
+        let source = self.source;
+        let mut verbatim = range.start;
+        let mut index = range.start;
+        while index < range.end {
+            let c = source[index..]
+                .chars()
+                .next()
+                .expect("index is inside range");
+            if c != '\\' {
+                index += c.len_utf8();
+                continue;
+            }
+            self.push_prose(verbatim..index);
+            let escaped = source[index + 1..range.end].chars().next();
+            let end = index + 1 + escaped.map_or(0, char::len_utf8);
+            match escaped {
+                Some('n') | Some('t') | Some('r') => self.map.push_replacement(" ", index..end),
+                Some(c @ '\\') | Some(c @ '"') | Some(c @ '\'') | Some(c @ '`') => {
+                    self.map.push_replacement(&c.to_string(), index..end)
+                }
+                _ => {}
+            }
+            index = end;
+            verbatim = index;
+        }
+        self.push_prose(verbatim..range.end);
+    }
+
+    /// Appends text copied from the source, splitting identifiers into words.
+    fn push_prose(&mut self, range: Range<usize>) {
+        let source = self.source;
+        if !self.options.split_identifiers {
+            self.map.push_verbatim(&source[range.clone()], range.start);
+            return;
+        }
+        let text = &source[range.clone()];
+        let mut copied = 0;
+        for identifier in identifier_regex().find_iter(text) {
+            self.map
+                .push_verbatim(&text[copied..identifier.start()], range.start + copied);
+            let mut word_start = identifier.start();
+            let mut previous = None;
+            for (offset, c) in identifier.as_str().char_indices() {
+                let index = identifier.start() + offset;
+                if c == '_' {
+                    if word_start < index {
+                        self.map
+                            .push_verbatim(&text[word_start..index], range.start + word_start);
+                        let separator_end = text[index..identifier.end()]
+                            .find(|c| c != '_')
+                            .map_or(identifier.end(), |end| index + end);
+                        self.map.push_replacement(
+                            " ",
+                            range.start + index..range.start + separator_end,
+                        );
+                    }
+                    word_start = index + 1;
+                } else if c.is_uppercase()
+                    && previous.is_some_and(|p: char| p.is_lowercase() || p.is_ascii_digit())
+                    && word_start < index
+                {
+                    self.map
+                        .push_verbatim(&text[word_start..index], range.start + word_start);
+                    self.map.push_inserted(" ", range.start + index);
+                    word_start = index;
+                }
+                previous = Some(c);
+            }
+            self.map.push_verbatim(
+                &text[word_start.min(identifier.end())..identifier.end()],
+                range.start + word_start.min(identifier.end()),
+            );
+            copied = identifier.end();
+        }
+        self.map
+            .push_verbatim(&text[copied..], range.start + copied);
+    }
+}
+
+/// Words written in camelCase or snake_case.
+fn identifier_regex() -> &'static Regex {
+    static IDENTIFIER: OnceLock<Regex> = OnceLock::new();
+    IDENTIFIER.get_or_init(|| {
+        Regex::new(
+            r"\b(?:[A-Za-z][A-Za-z0-9]*(?:_+[A-Za-z0-9]+)+|[a-z][a-z0-9]+(?:[A-Z][a-z0-9]*)+)\b",
+        )
+        .expect("identifier pattern is valid")
+    })
+}
+
+fn comment_marker(language: Language) -> &'static str {
+    if language.has_slash_comments() {
+        "//"
+    } else {
+        "#"
+    }
+}
+
+/// Whether `#` at `position` starts a shell comment rather than being part of a word.
+fn starts_word(source: &str, position: usize) -> bool {
+    source[..position]
+        .chars()
+        .next_back()
+        .is_none_or(|c| c.is_whitespace() || c == ';' || c == '(' || c == '|' || c == '&')
+}
+
+/// End of the line containing `position`, excluding a carriage return.
+fn line_end(source: &str, position: usize) -> usize {
+    let end = source[position..]
+        .find('\n')
+        .map_or(source.len(), |end| position + end);
+    if source[..end].ends_with('\r') {
+        end - 1
+    } else {
+        end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rust() {
+        let source = "//! Crate docs.\n\n/// Parses the `maxValue` of\n/// a parse_html_tree in JavaScript.\nfn f<'a>(c: char) -> &'a str {\n    let s = \"Hello\\nworld\"; // trailing\n    let r = r#\"raw \"text\"\"#;\n    /* Block\n     * comment */\n    'x'; s\n}\n";
+        let options = CodeOptions {
+            split_identifiers: true,
+            ..CodeOptions::default()
+        };
+        let fragments = extract(source, Language::Rust, &options);
+        let map = &fragments[0].map;
+        assert_eq!(
+            map.text(),
+            "Crate docs.\n\nParses the `max Value` of\na parse html tree in JavaScript.\n\ntrailing\n\nBlock\ncomment\n\n"
+        );
+        let text = map.text();
+        let range = |needle: &str| {
+            let start = text.find(needle).unwrap();
+            map.to_original(start..start + needle.len())
+        };
+        assert_eq!(&source[range("Value")], "Value");
+        assert_eq!(&source[range("html tree")], "html_tree");
+        assert_eq!(&source[range("Block\ncomment")], "Block\n     * comment");
+
+        let options = CodeOptions {
+            strings: true,
+            split_identifiers: false,
+        };
+        let fragments = extract(source, Language::Rust, &options);
+        assert!(fragments[0]
+            .map
+            .text()
+            .contains("Hello world\n\ntrailing\n\nraw \"text\"\n\n"));
+    }
+
+    #[test]
+    fn test_python_and_shell() {
+        let source = "#!/usr/bin/env python\ndef f():\n    \"\"\"Docstring here.\n\n    More text.\n    \"\"\"\n    x = '# not a comment'  # a comment\n";
+        let fragments = extract(source, Language::Python, &CodeOptions::default());
+        assert_eq!(
+            fragments[0].map.text(),
+            "Docstring here.\n\nMore text.\n\na comment\n\n"
+        );
+
+        let source = "echo \"#1\" a#b # note\n# next\n";
+        let fragments = extract(source, Language::Shell, &CodeOptions::default());
+        assert_eq!(fragments[0].map.text(), "note\nnext\n\n");
+    }
+}
@@ -0,0 +1,585 @@
+//! HTML and XML documents like XHTML or DocBook.
+
+use super::{end_block, Fragment};
+use std::ops::Range;
+use SourceMap;
+
+/// Inline elements whose contents are not prose, replaced by a placeholder
+/// so that the sentence around them stays grammatical.
+const PLACEHOLDER_ELEMENTS: &[&str] = &["code"];
+
+/// Elements whose contents are not prose.
+const SKIPPED_ELEMENTS: &[&str] = &[
+    "script",
+    "style",
+    "pre",
+    // DocBook
+    "programlisting",
+    "screen",
+];
+
+/// HTML elements that are never closed.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// Elements that do not interrupt a sentence, other elements start a new block of text.
+const INLINE_ELEMENTS: &[&str] = &[
+    "a",
+    "abbr",
+    "acronym",
+    "b",
+    "bdi",
+    "bdo",
+    "cite",
+    "data",
+    "del",
+    "dfn",
+    "em",
+    "emphasis",
+    "font",
+    "foreignphrase",
+    "i",
+    "img",
+    "ins",
+    "kbd",
+    "literal",
+    "mark",
+    "phrase",
+    "q",
+    "quote",
+    "s",
+    "samp",
+    "small",
+    "span",
+    "strong",
+    "sub",
+    "sup",
+    "time",
+    "u",
+    "var",
+];
+
+/// Attributes containing prose.
+const TEXT_ATTRIBUTES: &[&str] = &["alt", "title"];
+
+/// Named character references decoded in text, others are kept as they are.
+const ENTITIES: &[(&str, &str)] = &[
+    ("amp", "&"),
+    ("lt", "<"),
+    ("gt", ">"),
+    ("quot", "\""),
+    ("apos", "'"),
+    ("nbsp", "\u{a0}"),
+    ("shy", "\u{ad}"),
+    ("ndash", "–"),
+    ("mdash", "—"),
+    ("hellip", "…"),
+    ("lsquo", "‘"),
+    ("rsquo", "’"),
+    ("sbquo", "‚"),
+    ("ldquo", "“"),
+    ("rdquo", "”"),
+    ("bdquo", "„"),
+    ("laquo", "«"),
+    ("raquo", "»"),
+    ("copy", "©"),
+    ("reg", "®"),
+    ("trade", "™"),
+    ("deg", "°"),
+    ("times", "×"),
+];
+
+/// Extracts prose from an HTML document.
+///
+/// Text of elements and their `alt` and `title` attributes is extracted,
+/// contents of `script`, `style` and `pre` are skipped and inline `code` is replaced
+/// by a placeholder. Parts of the document
+/// marked with a `lang` attribute become separate fragments in that language.
+pub fn extract(source: &str) -> Vec<Fragment> {
+    Extractor::new(source, false, Vocabulary::default()).run()
+}
+
+/// Extracts prose from an XML document, like [`extract`] but with case-sensitive names,
+/// no void elements and CDATA sections. Both `xml:lang` and `lang` attributes set the language,
+/// the latter being used e.g. by DocBook 4.
+pub fn extract_xml(source: &str) -> Vec<Fragment> {
+    Extractor::new(source, true, Vocabulary::default()).run()
+}
+
+/// Elements of an XML vocabulary, in addition to the ones known to [`extract_xml`].
+#[derive(Default)]
+pub(super) struct Vocabulary<'a> {
+    /// Elements whose contents are not prose.
+    pub skipped: &'a [&'a str],
+    /// Elements that do not interrupt a sentence.
+    pub inline: &'a [&'a str],
+    /// Inline elements replaced by a placeholder.
+    pub placeholders: &'a [&'a str],
+}
+
+/// Extracts prose from an XML document using the elements of given vocabulary.
+pub(super) fn extract_xml_with(source: &str, vocabulary: Vocabulary) -> Vec<Fragment> {
+    Extractor::new(source, true, vocabulary).run()
+}
+
+struct Element {
+    name: String,
+    language: Option<String>,
+    /// Start of the element when it is replaced by a placeholder.
+    placeholder: Option<usize>,
+}
+
+struct Extractor<'a> {
+    source: &'a str,
+    xml: bool,
+    vocabulary: Vocabulary<'a>,
+    /// Open elements.
+    stack: Vec<Element>,
+    fragments: Vec<Fragment>,
+    current: Fragment,
+}
+
+impl<'a> Extractor<'a> {
+    fn new(source: &'a str, xml: bool, vocabulary: Vocabulary<'a>) -> Self {
+        Self {
+            source,
+            xml,
+            vocabulary,
+            stack: Vec::new(),
+            fragments: Vec::new(),
+            current: Fragment::default(),
+        }
+    }
+
+    fn run(mut self) -> Vec<Fragment> {
+        let source = self.source;
+        let mut position = 0;
+        while position < source.len() {
+            let rest = &source[position..];
+            if !rest.starts_with('<') {
+                let end = rest.find('<').map_or(source.len(), |end| position + end);
+                self.text(position..end, true);
+                position = end;
+            } else if rest.starts_with("<!--") {
+                position = after(source, position, "-->");
+            } else if rest.starts_with("<![CDATA[") {
+                let start = position + "<![CDATA[".len();
+                let end = source[start..]
+                    .find("]]>")
+                    .map_or(source.len(), |end| start + end);
+                self.text(start..end, false);
+                position = after(source, end, "]]>");
+            } else if rest.starts_with("</") {
+                let end = after(source, position, ">");
+                let name = source[position + 2..end]
+                    .trim_end_matches('>')
+                    .trim()
+                    .to_string();
+                self.close(&self.normalize(&name), end);
+                position = end;
+            } else if rest[1..].starts_with(|c: char| c.is_alphabetic()) {
+                position = self.open(position);
+            } else if rest.starts_with("<!") || rest.starts_with("<?") {
+                position = after(source, position, ">");
+            } else {
+                self.text(position..position + 1, true);
+                position += 1;
+            }
+        }
+        self.finish_fragment();
+        self.fragments
+    }
+
+    fn normalize(&self, name: &str) -> String {
+        if self.xml {
+            name.to_string()
+        } else {
+            name.to_lowercase()
+        }
+    }
+
+    fn is(&self, name: &str, list: &[&str]) -> bool {
+        // Ignore namespace prefixes in XML.
+        let local = name.rsplit(':').next().unwrap_or(name);
+        list.contains(&if self.xml { local } else { name })
+    }
+
+    fn is_inline(&self, name: &str) -> bool {
+        self.is(name, INLINE_ELEMENTS)
+            || self.is(name, self.vocabulary.inline)
+            || self.is_placeholder(name)
+    }
+
+    fn is_skipped(&self, name: &str) -> bool {
+        self.is(name, SKIPPED_ELEMENTS)
+            || self.is(name, self.vocabulary.skipped)
+            || self.is_placeholder(name)
+    }
+
+    fn is_placeholder(&self, name: &str) -> bool {
+        self.is(name, PLACEHOLDER_ELEMENTS) || self.is(name, self.vocabulary.placeholders)
+    }
+
+    fn language(&self) -> Option<String> {
+        self.stack
+            .iter()
+            .rev()
+            .find_map(|element| element.language.clone())
+    }
+
+    fn skipping(&self) -> bool {
+        self.stack
+            .iter()
+            .any(|element| self.is_skipped(&element.name))
+    }
+
+    fn finish_fragment(&mut self) {
+        let language = self.language();
+        let fragment = std::mem::replace(
+            &mut self.current,
+            Fragment {
+                map: SourceMap::new(),
+                language,
+            },
+        );
+        if !fragment.map.text().trim().is_empty() {
+            self.fragments.push(fragment);
+        }
+    }
+
+    /// Starts a new fragment when the language changed.
+    fn update_language(&mut self) {
+        if self.language() != self.current.language {
+            self.finish_fragment();
+        }
+    }
+
+    /// Parses a start tag at `position` and returns the position after it.
+    fn open(&mut self, position: usize) -> usize {
+        let source = self.source;
+        let tag = Tag::parse(source, position);
+        let name = self.normalize(&source[tag.name.clone()]);
+
+        let mut language = None;
+        let mut texts = Vec::new();
+        for (attribute, value) in &tag.attributes {
+            let attribute = self.normalize(&source[attribute.clone()]);
+            if attribute == "xml:lang" || attribute == "lang" {
+                language = Some(decode(&source[value.clone()]));
+            } else if TEXT_ATTRIBUTES.contains(&attribute.as_str()) {
+                texts.push(value.clone());
+            }
+        }
+
+        let closed = tag.self_closing || (!self.xml && self.is(&name, VOID_ELEMENTS));
+        let placeholder = if self.is_placeholder(&name) && !self.skipping() {
+            Some(position)
+        } else {
+            None
+        };
+        if self.is(&name, &["br"]) {
+            // Line breaks do not end sentences, like hard breaks in Markdown.
+            if !self.skipping() {
+                self.current.map.push_replacement("\n", position..tag.end);
+            }
+        } else if !self.is_inline(&name) {
+            end_block(&mut self.current.map, position);
+        }
+        self.stack.push(Element {
+            name: name.clone(),
+            language,
+            placeholder,
+        });
+
+        if !self.skipping() {
+            let language = self.language();
+            for value in texts {
+                let mut fragment = Fragment {
+                    map: SourceMap::new(),
+                    language: language.clone(),
+                };
+                push_text(&mut fragment.map, source, value, true);
+                self.fragments.push(fragment);
+            }
+        }
+
+        let mut end = tag.end;
+        if closed {
+            self.stack.pop();
+            if placeholder.is_some() {
+                self.current.map.push_placeholder(position..end);
+            }
+        } else {
+            self.update_language();
+            // Raw text elements can contain `<` without starting a tag.
+            if !self.xml && (name == "script" || name == "style") {
+                let closing = format!("</{}", name);
+                end = find_ignore_ascii_case(&source[end..], &closing)
+                    .map_or(source.len(), |offset| end + offset);
+            }
+        }
+        end
+    }
+
+    fn close(&mut self, name: &str, end: usize) {
+        if let Some(index) = self.stack.iter().rposition(|element| element.name == name) {
+            let placeholder = self.stack[index].placeholder;
+            self.stack.truncate(index);
+            if let Some(start) = placeholder {
+                self.current.map.push_placeholder(start..end);
+            }
+        }
+        if !self.is_inline(name) {
+            end_block(&mut self.current.map, end);
+        }
+        self.update_language();
+    }
+
+    fn text(&mut self, range: Range<usize>, entities: bool) {
+        if !self.skipping() {
+            push_text(&mut self.current.map, self.source, range, entities);
+        }
+    }
+}
+
+/// Start tag with ranges of its name and attributes.
+struct Tag {
+    name: Range<usize>,
+    /// Names and values of attributes, without quotes.
+    attributes: Vec<(Range<usize>, Range<usize>)>,
+    self_closing: bool,
+    /// Position after the tag.
+    end: usize,
+}
+
+impl Tag {
+    fn parse(source: &str, start: usize) -> Self {
+        let bytes = source.as_bytes();
+        let is_name_end = |b: u8| b.is_ascii_whitespace() || b == b'>' || b == b'/' || b == b'=';
+        let name_end = |mut position: usize| {
+            while position < bytes.len() && !is_name_end(bytes[position]) {
+                position += 1;
+            }
+            position
+        };
+        let skip_whitespace = |mut position: usize| {
+            while position < bytes.len() && bytes[position].is_ascii_whitespace() {
+                position += 1;
+            }
+            position
+        };
+
+        let name = start + 1..name_end(start + 1);
+        let mut attributes = Vec::new();
+        let mut position = name.end;
+        let mut self_closing = false;
+        loop {
+            position = skip_whitespace(position);
+            match bytes.get(position) {
+                None => break,
+                Some(b'>') => {
+                    position += 1;
+                    break;
+                }
+                Some(b'/') => {
+                    self_closing = bytes.get(position + 1) == Some(&b'>');
+                    position += 1;
+                    continue;
+                }
+                _ => {}
+            }
+            let attribute = position..name_end(position).max(position + 1);
+            position = skip_whitespace(attribute.end);
+            let value = if bytes.get(position) == Some(&b'=') {
+                position = skip_whitespace(position + 1);
+                match bytes.get(position) {
+                    Some(&quote) if quote == b'"' || quote == b'\'' => {
+                        let value_start = position + 1;
+                        let value_end = source[value_start..]
+                            .find(quote as char)
+                            .map_or(source.len(), |end| value_start + end);
+                        position = (value_end + 1).min(source.len());
+                        value_start..value_end
+                    }
+                    _ => {
+                        let value_start = position;
+                        while position < bytes.len()
+                            && !bytes[position].is_ascii_whitespace()
+                            && bytes[position] != b'>'
+                        {
+                            position += 1;
+                        }
+                        value_start..position
+                    }
+                }
+            } else {
+                attribute.end..attribute.end
+            };
+            attributes.push((attribute, value));
+        }
+
+        Self {
+            name,
+            attributes,
+            self_closing,
+            end: position,
+        }
+    }
+}
+
+/// Finds an ASCII `needle` in `haystack` regardless of case, returning its byte offset.
+fn find_ignore_ascii_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// Returns the position after the first `needle` following `position`, or the end of source.
+fn after(source: &str, position: usize, needle: &str) -> usize {
+    source[position..]
+        .find(needle)
+        .map_or(source.len(), |offset| position + offset + needle.len())
+}
+
+/// Decodes a character reference at the start of text, returning the character and its length.
+fn entity(text: &str) -> Option<(String, usize)> {
+    let end = text.find(';').filter(|&end| end <= 32)?;
+    let name = &text[1..end];
+    let decoded = if let Some(number) = name.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        char::from_u32(code)?.to_string()
+    } else {
+        ENTITIES
+            .iter()
+            .find(|&&(entity, _)| entity == name)
+            .map(|&(_, decoded)| decoded.to_string())?
+    };
+    Some((decoded, end + 1))
+}
+
+/// Decodes character references of an attribute value.
+pub(super) fn decode(text: &str) -> String {
+    let mut map = SourceMap::new();
+    push_text(&mut map, text, 0..text.len(), true);
+    map.text().trim().to_string()
+}
+
+/// Appends text to the map, decoding character references and collapsing whitespace.
+fn push_text(map: &mut SourceMap, source: &str, range: Range<usize>, entities: bool) {
+    let text = &source[range.clone()];
+    let mut verbatim_start = 0;
+    let mut position = 0;
+    while position < text.len() {
+        let rest = &text[position..];
+        let c = rest.chars().next().expect("position is inside text");
+        let replacement = if c.is_whitespace() {
+            let length = rest.len() - rest.trim_start().len();
+            let at_block_start = map.text().is_empty() || map.text().ends_with('\n');
+            if at_block_start && verbatim_start == position {
+                Some((String::new(), length))
+            } else if length > 1 || c != ' ' {
+                Some((" ".to_string(), length))
+            } else {
+                None
+            }
+        } else if c == '&' && entities {
+            entity(rest)
+        } else {
+            None
+        };
+
+        match replacement {
+            Some((replacement, length)) => {
+                let start = range.start + verbatim_start;
+                map.push_verbatim(&text[verbatim_start..position], start);
+                let replaced = range.start + position..range.start + position + length;
+                map.push_replacement(&replacement, replaced);
+                position += length;
+                verbatim_start = position;
+            }
+            None => position += c.len_utf8(),
+        }
+    }
+    map.push_verbatim(&text[verbatim_start..], range.start + verbatim_start);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(fragments: &[Fragment]) -> Vec<(&str, Option<&str>)> {
+        fragments
+            .iter()
+            .map(|fragment| (fragment.map.text(), fragment.language.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn test_html() {
+        let source = r#"<!DOCTYPE html>
+<html lang="en"><head><title>Tou manny</title><style>p < a {}</style></head>
+<body>
+  <p>Fish &amp; chips&nbsp;&#x2014; <em>tasty</em><br>
+  <img src="a.png" alt="A  logo">
+  <span lang="de">Guten Tag</span>!</p>
+  <!-- <p>comment</p> -->
+  <pre>let x = 1;</pre><p>Use <code>x</code>.</p>
+  <script>if (a < b) {}</script>
+</body></html>"#;
+        let fragments = extract(source);
+        assert_eq!(
+            texts(&fragments),
+            vec![
+                ("A logo", Some("en")),
+                ("Tou manny\n\nFish & chips\u{a0}— tasty\n", Some("en")),
+                ("Guten Tag", Some("de")),
+                ("!\n\nUse X.\n\n", Some("en")),
+            ]
+        );
+
+        let fragment = &fragments[1];
+        let text = fragment.map.text();
+        let range = |needle: &str| {
+            let start = text.find(needle).unwrap();
+            fragment.map.to_original(start..start + needle.len())
+        };
+        assert_eq!(&source[range("Fish & chips")], "Fish &amp; chips");
+        assert_eq!(&source[range("tasty")], "tasty");
+        assert_eq!(&source[fragments[2].map.to_original(0..5)], "Guten");
+        assert_eq!(
+            &source[fragments[3].map.to_original(7..8)],
+            "<code>x</code>"
+        );
+        assert!(fragments[3].map.in_placeholder(&(7..8)));
+    }
+
+    #[test]
+    fn test_raw_text() {
+        // Lowercasing `İ` changes its length, the closing tag must still be found in place.
+        let source = "<SCRIPT>let s = \"İİ\";</Script><p>After</p>";
+        let fragments = extract(source);
+        assert_eq!(texts(&fragments), vec![("After\n\n", None)]);
+        assert_eq!(&source[fragments[0].map.to_original(0..5)], "After");
+    }
+
+    #[test]
+    fn test_xml() {
+        let source = r#"<?xml version="1.0"?>
+<article xml:lang="cs"><title>Nadpis</title>
+<para>Text <emphasis>důležitý</emphasis> <![CDATA[a < b]]></para>
+<programlisting>skipped</programlisting><para lang="en">English</para></article>"#;
+        assert_eq!(
+            texts(&extract_xml(source)),
+            vec![
+                ("Nadpis\n\nText důležitý a < b\n\n", Some("cs")),
+                ("English\n\n", Some("en")),
+            ]
+        );
+    }
+}
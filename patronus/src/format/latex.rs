@@ -0,0 +1,672 @@
+//! LaTeX documents.
+
+use super::{end_block, Fragment};
+use std::collections::HashMap;
+use std::ops::Range;
+use SourceMap;
+
+/// What to do with a macro and its arguments.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MacroRule {
+    /// Removes the macro and its optional arguments, keeping the text of the others,
+    /// e.g. `\emph{text}`.
+    Keep,
+    /// Like `Keep` but the text of the arguments forms a separate block, e.g. `\section{Title}`.
+    Block,
+    /// The text of the arguments is checked as a separate fragment without interrupting
+    /// the surrounding sentence, e.g. `\footnote{text}`.
+    Aside,
+    /// Removes the macro with its optional arguments and given number of mandatory ones,
+    /// e.g. `\label{key}`. Arguments beyond that are kept, e.g. the text of `\href{url}{text}`.
+    Drop { arguments: usize },
+    /// Replaces the macro with its optional arguments and given number of mandatory ones
+    /// by a placeholder, so that the sentence stays whole, e.g. `\cite{key}`.
+    Placeholder { arguments: usize },
+    /// Replaces the macro with its optional arguments and given number of mandatory ones by text,
+    /// e.g. `\LaTeX`.
+    Replace { text: String, arguments: usize },
+    /// Replaces the macro with its argument, delimited by any character, by a placeholder,
+    /// e.g. `\verb|code|`.
+    Verbatim,
+}
+
+/// Accent commands with the combining mark they add, the letters with a precomposed
+/// accented form and those forms, e.g. `\'e` is `é`. Other letters get the combining mark.
+const ACCENTS: &[(&str, char, &str, &str)] = &[
+    (
+        "'",
+        '\u{301}',
+        "aeiouyAEIOUYcnszCNSZlrLR",
+        "áéíóúýÁÉÍÓÚÝćńśźĆŃŚŹĺŕĹŔ",
+    ),
+    ("`", '\u{300}', "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+    ("^", '\u{302}', "aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+    ("\"", '\u{308}', "aeiouyAEIOUY", "äëïöüÿÄËÏÖÜŸ"),
+    ("~", '\u{303}', "anoANO", "ãñõÃÑÕ"),
+    ("=", '\u{304}', "aeiouAEIOU", "āēīōūĀĒĪŌŪ"),
+    (".", '\u{307}', "zeZEI", "żėŻĖİ"),
+    ("H", '\u{30b}', "ouOU", "őűŐŰ"),
+    ("c", '\u{327}', "csCS", "çşÇŞ"),
+    ("v", '\u{30c}', "cdenrstzCDENRSTZ", "čďěňřšťžČĎĚŇŘŠŤŽ"),
+    ("u", '\u{306}', "agAG", "ăğĂĞ"),
+    ("k", '\u{328}', "aeAE", "ąęĄĘ"),
+    ("r", '\u{30a}', "auAU", "åůÅŮ"),
+];
+
+/// What to do with an environment.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EnvironmentRule {
+    /// Checks the contents, after removing given number of mandatory arguments
+    /// of `\begin`, e.g. column specification of `tabular`.
+    Keep { arguments: usize },
+    /// Skips the whole environment, e.g. `equation`.
+    Skip,
+}
+
+/// Rules for LaTeX commands, unknown macros are kept and unknown environments checked.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LatexOptions {
+    pub macros: HashMap<String, MacroRule>,
+    pub environments: HashMap<String, EnvironmentRule>,
+}
+
+impl Default for LatexOptions {
+    /// Rules for the commands of LaTeX and common packages.
+    fn default() -> Self {
+        let mut macros = HashMap::new();
+        for name in &[
+            "part",
+            "chapter",
+            "section",
+            "subsection",
+            "subsubsection",
+            "paragraph",
+            "subparagraph",
+            "title",
+            "caption",
+            "item",
+            "par",
+        ] {
+            macros.insert(name.to_string(), MacroRule::Block);
+        }
+        for name in &["footnote", "marginpar"] {
+            macros.insert(name.to_string(), MacroRule::Aside);
+        }
+        for &(name, arguments) in &[
+            ("cite", 1),
+            ("citep", 1),
+            ("citet", 1),
+            ("citeauthor", 1),
+            ("ref", 1),
+            ("eqref", 1),
+            ("pageref", 1),
+            ("autoref", 1),
+            ("cref", 1),
+            ("Cref", 1),
+            ("url", 1),
+            ("texttt", 1),
+            ("SI", 2),
+            ("si", 1),
+            ("num", 1),
+        ] {
+            macros.insert(name.to_string(), MacroRule::Placeholder { arguments });
+        }
+        macros.insert("verb".to_string(), MacroRule::Verbatim);
+        for &(name, arguments) in &[
+            ("label", 1),
+            ("href", 1),
+            ("includegraphics", 1),
+            ("input", 1),
+            ("include", 1),
+            ("usepackage", 1),
+            ("documentclass", 1),
+            ("bibliography", 1),
+            ("bibliographystyle", 1),
+            ("addbibresource", 1),
+            ("newcommand", 2),
+            ("renewcommand", 2),
+            ("setlength", 2),
+            ("vspace", 1),
+            ("hspace", 1),
+            ("maketitle", 0),
+            ("tableofcontents", 0),
+            ("newpage", 0),
+            ("clearpage", 0),
+            ("centering", 0),
+            ("noindent", 0),
+            ("hline", 0),
+        ] {
+            macros.insert(name.to_string(), MacroRule::Drop { arguments });
+        }
+        for &(name, text) in &[
+            ("LaTeX", "LaTeX"),
+            ("TeX", "TeX"),
+            ("ldots", "…"),
+            ("dots", "…"),
+            ("textendash", "–"),
+            ("textemdash", "—"),
+        ] {
+            macros.insert(
+                name.to_string(),
+                MacroRule::Replace {
+                    text: text.to_string(),
+                    arguments: 0,
+                },
+            );
+        }
+
+        let mut environments = HashMap::new();
+        for name in &[
+            "equation",
+            "equation*",
+            "align",
+            "align*",
+            "gather",
+            "gather*",
+            "multline",
+            "multline*",
+            "eqnarray",
+            "eqnarray*",
+            "math",
+            "displaymath",
+            "verbatim",
+            "lstlisting",
+            "minted",
+            "comment",
+            "tikzpicture",
+            "thebibliography",
+        ] {
+            environments.insert(name.to_string(), EnvironmentRule::Skip);
+        }
+        for name in &["tabular", "tabular*", "array", "tabularx"] {
+            let arguments = if name.ends_with('*') || *name == "tabularx" {
+                2
+            } else {
+                1
+            };
+            environments.insert(name.to_string(), EnvironmentRule::Keep { arguments });
+        }
+
+        Self {
+            macros,
+            environments,
+        }
+    }
+}
+
+/// Extracts prose from a LaTeX document.
+///
+/// Only the body of `document` environment is checked when present. Comments are skipped,
+/// inline math is replaced by a placeholder, macros and environments are handled according
+/// to the options. Footnotes and other asides follow the main text as separate fragments.
+pub fn extract(source: &str, options: &LatexOptions) -> Vec<Fragment> {
+    let body = match source.find("\\begin{document}") {
+        Some(start) => {
+            let start = start + "\\begin{document}".len();
+            let end = source[start..]
+                .find("\\end{document}")
+                .map_or(source.len(), |end| start + end);
+            start..end
+        }
+        None => 0..source.len(),
+    };
+    let mut extractor = Extractor {
+        source,
+        options,
+        map: SourceMap::new(),
+        asides: Vec::new(),
+    };
+    extractor.process(body);
+    let main = Fragment {
+        map: extractor.map,
+        language: None,
+    };
+    std::iter::once(main).chain(extractor.asides).collect()
+}
+
+struct Extractor<'a> {
+    source: &'a str,
+    options: &'a LatexOptions,
+    map: SourceMap,
+    /// Texts of `MacroRule::Aside` macros.
+    asides: Vec<Fragment>,
+}
+
+impl<'a> Extractor<'a> {
+    fn process(&mut self, range: Range<usize>) {
+        let source = self.source;
+        let mut position = range.start;
+        // Start of text to be copied as it is.
+        let mut verbatim = position;
+        while position < range.end {
+            let rest = &source[position..range.end];
+            let c = rest.chars().next().expect("position is inside range");
+            let (replacement, next) = match c {
+                '\\' => {
+                    self.flush(verbatim..position);
+                    position = self.command(position, range.end);
+                    verbatim = position;
+                    continue;
+                }
+                '%' => {
+                    let end = rest.find('\n').map_or(range.end, |end| position + end);
+                    (None, end)
+                }
+                '$' => {
+                    let delimiter = if rest.starts_with("$$") { "$$" } else { "$" };
+                    let end = closing(source, position + delimiter.len(), range.end, delimiter);
+                    self.flush(verbatim..position);
+                    self.map.push_placeholder(position..end);
+                    position = end;
+                    verbatim = position;
+                    continue;
+                }
+                '{' | '}' => (None, position + 1),
+                '~' => (Some("\u{a0}"), position + 1),
+                '&' => {
+                    self.flush(verbatim..position);
+                    end_block(&mut self.map, position);
+                    position += 1;
+                    verbatim = position;
+                    continue;
+                }
+                '-' if rest.starts_with("---") => (Some("—"), position + 3),
+                '-' if rest.starts_with("--") => (Some("–"), position + 2),
+                '`' if rest.starts_with("``") => (Some("“"), position + 2),
+                '\'' if rest.starts_with("''") => (Some("”"), position + 2),
+                c if c.is_whitespace() => {
+                    let length = rest.len() - rest.trim_start().len();
+                    let whitespace = &rest[..length];
+                    self.flush(verbatim..position);
+                    if whitespace.matches('\n').count() > 1 {
+                        end_block(&mut self.map, position);
+                    } else if !self.map.text().is_empty()
+                        && !self.map.text().ends_with(char::is_whitespace)
+                    {
+                        if whitespace == " " {
+                            self.map.push_verbatim(" ", position);
+                        } else {
+                            self.map.push_replacement(" ", position..position + length);
+                        }
+                    }
+                    position += length;
+                    verbatim = position;
+                    continue;
+                }
+                c => {
+                    position += c.len_utf8();
+                    continue;
+                }
+            };
+            self.flush(verbatim..position);
+            if let Some(replacement) = replacement {
+                self.map.push_replacement(replacement, position..next);
+            }
+            position = next;
+            verbatim = position;
+        }
+        self.flush(verbatim..position.min(range.end));
+    }
+
+    fn flush(&mut self, range: Range<usize>) {
+        self.map
+            .push_verbatim(&self.source[range.clone()], range.start);
+    }
+
+    /// Handles a command at `position` and returns the position after it.
+    fn command(&mut self, position: usize, limit: usize) -> usize {
+        let source = self.source;
+        let name_start = position + 1;
+        let name_end = source[name_start..limit]
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .map_or(limit, |end| name_start + end);
+        if name_end == name_start {
+            // Control symbol like `\%` or `\\`.
+            let symbol = match source[name_start..limit].chars().next() {
+                Some(symbol) => symbol,
+                None => return limit,
+            };
+            let end = name_start + symbol.len_utf8();
+            if let Some(accent) = ACCENTS
+                .iter()
+                .find(|accent| accent.0 == &source[name_start..end])
+            {
+                return self.accent(position, end, limit, accent, false);
+            }
+            match symbol {
+                '%' | '&' | '$' | '#' | '_' | '{' | '}' => self
+                    .map
+                    .push_replacement(&symbol.to_string(), position..end),
+                '(' | '[' => {
+                    let delimiter = if symbol == '(' { "\\)" } else { "\\]" };
+                    let end = closing(source, end, limit, delimiter);
+                    self.map.push_placeholder(position..end);
+                    return end;
+                }
+                '\\' => {
+                    let end = optional_arguments(source, end, limit);
+                    if !self.map.text().ends_with(char::is_whitespace) {
+                        self.map.push_replacement(" ", position..end);
+                    }
+                    return end;
+                }
+                // Explicit and thin spaces.
+                ' ' | ',' | ';' | ':' => self.map.push_replacement(" ", position..end),
+                _ => {}
+            }
+            return end;
+        }
+
+        let name = &source[name_start..name_end];
+        if name == "begin" || name == "end" {
+            return self.environment(name == "begin", name_end, limit);
+        }
+        if !self.options.macros.contains_key(name) {
+            if let Some(accent) = ACCENTS.iter().find(|accent| accent.0 == name) {
+                return self.accent(position, name_end, limit, accent, true);
+            }
+        }
+        let mut end = optional_arguments(source, name_end, limit);
+        match self.options.macros.get(name) {
+            Some(&MacroRule::Drop { arguments }) => {
+                mandatory_arguments(source, &mut end, limit, arguments);
+            }
+            Some(&MacroRule::Placeholder { arguments }) => {
+                mandatory_arguments(source, &mut end, limit, arguments);
+                self.map.push_placeholder(position..end);
+            }
+            Some(&MacroRule::Verbatim) => {
+                end = verb(source, name_end, limit);
+                self.map.push_placeholder(position..end);
+            }
+            Some(&MacroRule::Replace {
+                ref text,
+                arguments,
+            }) => {
+                mandatory_arguments(source, &mut end, limit, arguments);
+                self.map.push_replacement(text, position..end);
+            }
+            Some(&MacroRule::Block) => {
+                end_block(&mut self.map, position);
+                while let Some(group) = group(source, skip_spaces(source, end, limit), limit) {
+                    self.process(group.start + 1..group.end - 1);
+                    end = group.end;
+                }
+                end_block(&mut self.map, end);
+            }
+            Some(&MacroRule::Aside) => {
+                let outer = std::mem::take(&mut self.map);
+                while let Some(group) = group(source, skip_spaces(source, end, limit), limit) {
+                    self.process(group.start + 1..group.end - 1);
+                    end = group.end;
+                }
+                end_block(&mut self.map, end);
+                let map = std::mem::replace(&mut self.map, outer);
+                self.asides.push(Fragment {
+                    map,
+                    language: None,
+                });
+            }
+            Some(&MacroRule::Keep) | None => {}
+        }
+        // Spaces after control words are not printed.
+        if end == name_end {
+            end = skip_spaces(source, end, limit);
+        }
+        end
+    }
+
+    /// Handles the argument of an accent command at `position` whose name ends at `name_end`,
+    /// returns the position after it. Accents of anything but a letter are dropped.
+    fn accent(
+        &mut self,
+        position: usize,
+        name_end: usize,
+        limit: usize,
+        accent: &(&str, char, &str, &str),
+        word: bool,
+    ) -> usize {
+        let source = self.source;
+        // Spaces after control words are skipped, after control symbols they are the argument.
+        let start = if word {
+            skip_spaces(source, name_end, limit)
+        } else {
+            name_end
+        };
+        let (argument, end) = match group(source, start, limit) {
+            Some(group) => (source[group.start + 1..group.end - 1].trim(), group.end),
+            None => match source[start..limit].chars().next() {
+                Some(c) => (&source[start..start + c.len_utf8()], start + c.len_utf8()),
+                None => return limit,
+            },
+        };
+        // Dotless letters are used so that the accent replaces the dot.
+        let argument = match argument {
+            "\\i" => "i",
+            "\\j" => "j",
+            argument => argument,
+        };
+        let mut chars = argument.chars();
+        let letter = match (chars.next(), chars.next()) {
+            (Some(letter), None) if letter.is_alphabetic() => letter,
+            _ => return name_end,
+        };
+        let &(_, mark, letters, composed) = accent;
+        let accented = match letters.chars().position(|c| c == letter) {
+            Some(index) => composed
+                .chars()
+                .nth(index)
+                .expect("accent tables have the same length")
+                .to_string(),
+            None => format!("{}{}", letter, mark),
+        };
+        self.map.push_replacement(&accented, position..end);
+        end
+    }
+
+    fn environment(&mut self, begin: bool, position: usize, limit: usize) -> usize {
+        let source = self.source;
+        let group = match group(source, position, limit) {
+            Some(group) => group,
+            None => return position,
+        };
+        let name = &source[group.start + 1..group.end - 1];
+        end_block(&mut self.map, position);
+        if !begin {
+            return group.end;
+        }
+        let mut end = optional_arguments(source, group.end, limit);
+        match self.options.environments.get(name) {
+            Some(&EnvironmentRule::Skip) => {
+                let closing_tag = format!("\\end{{{}}}", name);
+                closing(source, end, limit, &closing_tag)
+            }
+            Some(&EnvironmentRule::Keep { arguments }) => {
+                mandatory_arguments(source, &mut end, limit, arguments);
+                end
+            }
+            None => end,
+        }
+    }
+}
+
+/// Skips spaces and tabs.
+fn skip_spaces(source: &str, position: usize, limit: usize) -> usize {
+    source[position..limit]
+        .find(|c| c != ' ' && c != '\t')
+        .map_or(limit, |offset| position + offset)
+}
+
+/// Returns the position after `delimiter` following `position`, or the limit.
+fn closing(source: &str, position: usize, limit: usize, delimiter: &str) -> usize {
+    source[position.min(limit)..limit]
+        .find(delimiter)
+        .map_or(limit, |offset| position + offset + delimiter.len())
+}
+
+/// Finds a balanced group delimited by `{}` starting at `position`, `None` when unclosed.
+fn group(source: &str, position: usize, limit: usize) -> Option<Range<usize>> {
+    balanced(source, position, limit, '{', '}')
+}
+
+fn balanced(
+    source: &str,
+    position: usize,
+    limit: usize,
+    open: char,
+    close: char,
+) -> Option<Range<usize>> {
+    if !source[position..limit].starts_with(open) {
+        return None;
+    }
+    let mut depth = 0;
+    let mut escaped = false;
+    for (offset, c) in source[position..limit].char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == open => depth += 1,
+            c if c == close => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(position..position + offset + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Skips a star and optional arguments in brackets.
+fn optional_arguments(source: &str, mut position: usize, limit: usize) -> usize {
+    if source[position..limit].starts_with('*') {
+        position += 1;
+    }
+    loop {
+        let start = skip_spaces(source, position, limit);
+        match balanced(source, start, limit, '[', ']') {
+            Some(argument) => position = argument.end,
+            None => return position,
+        }
+    }
+}
+
+/// Skips up to `count` mandatory arguments.
+fn mandatory_arguments(source: &str, position: &mut usize, limit: usize, count: usize) {
+    for _ in 0..count {
+        match group(source, skip_spaces(source, *position, limit), limit) {
+            Some(argument) => *position = optional_arguments(source, argument.end, limit),
+            None => return,
+        }
+    }
+}
+
+/// Skips the argument of `\verb`, delimited by any character.
+fn verb(source: &str, position: usize, limit: usize) -> usize {
+    let position = optional_arguments(source, position, limit);
+    match source[position..limit].chars().next() {
+        Some(delimiter) => closing(
+            source,
+            position + delimiter.len_utf8(),
+            limit,
+            &delimiter.to_string(),
+        ),
+        None => limit,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        let source = r"\documentclass{article}
+\usepackage{amsmath}
+\begin{document}
+\section*{Introduction}
+As shown by \citet[p.~5]{knuth}, \emph{mistakes} are good -- see Figure~\ref{fig:one}
+and $x^2$ or \(y\). % a comment
+Read \href{https://example.com}{the \LaTeX{} docs}\footnote{Really.} and \verb|code|.
+
+\begin{equation}
+  a = b
+\end{equation}
+\begin{tabular}{ll}
+  One & Two \\
+\end{tabular}
+\end{document}
+";
+        let options = LatexOptions::default();
+        let fragments = extract(source, &options);
+        let text = fragments[0].map.text();
+        assert_eq!(
+            text,
+            "Introduction\n\nAs shown by X, mistakes are good – see Figure\u{a0}X and X or X. Read the LaTeX docs and X.\n\nOne \n\nTwo \n\n"
+        );
+        assert_eq!(fragments.len(), 2);
+        assert_eq!(fragments[1].map.text(), "Really.\n\n");
+        assert_eq!(&source[fragments[1].map.to_original(0..7)], "Really.");
+
+        let range = |needle: &str| {
+            let start = text.find(needle).unwrap();
+            fragments[0].map.to_original(start..start + needle.len())
+        };
+        assert_eq!(&source[range("mistakes are")], "mistakes} are");
+        assert_eq!(&source[range("the LaTeX docs")], "the \\LaTeX{} docs");
+        assert_eq!(&source[range("X,")], "\\citet[p.~5]{knuth},");
+        assert_eq!(&source[range("X or X")], "$x^2$ or \\(y\\)");
+        let placeholder = text.find("X,").unwrap();
+        assert!(fragments[0]
+            .map
+            .in_placeholder(&(placeholder..placeholder + 1)));
+    }
+
+    #[test]
+    fn test_accents() {
+        for &(name, _, letters, composed) in ACCENTS {
+            assert_eq!(
+                letters.chars().count(),
+                composed.chars().count(),
+                "{}",
+                name
+            );
+        }
+        let source = "Caf\\'e na\\\"{\\i}ve, Erd\\H{o}s, \\v c\\'{\\i}sla, \\c{c}a, \\'{}x 10\\,km a\\;b \\~w";
+        let fragments = extract(source, &LatexOptions::default());
+        let text = fragments[0].map.text();
+        assert_eq!(text, "Café naïve, Erdős, čísla, ça, x 10 km a b w\u{303}");
+        let start = text.find("Erdős").unwrap();
+        let range = fragments[0].map.to_original(start..start + "Erdős".len());
+        assert_eq!(&source[range], "Erd\\H{o}s");
+    }
+
+    #[test]
+    fn test_unbalanced() {
+        assert_eq!(balanced("{a{b}", 0, 5, '{', '}'), None);
+        let fragments = extract("\\section{Unclosed title", &LatexOptions::default());
+        assert_eq!(fragments[0].map.text(), "Unclosed title");
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let mut options = LatexOptions::default();
+        options
+            .macros
+            .insert("todo".to_string(), MacroRule::Drop { arguments: 1 });
+        options.macros.insert(
+            "product".to_string(),
+            MacroRule::Replace {
+                text: "Patronus".to_string(),
+                arguments: 0,
+            },
+        );
+        options
+            .environments
+            .insert("code".to_string(), EnvironmentRule::Skip);
+        let source = "Use \\product\\todo{fix} now.\\begin{code}x\\end{code}";
+        let fragments = extract(source, &options);
+        assert_eq!(fragments[0].map.text(), "Use Patronus now.\n\n");
+    }
+}
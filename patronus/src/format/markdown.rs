@@ -0,0 +1,162 @@
+//! CommonMark with the common extensions (tables, footnotes, strikethrough, task lists).
+
+use super::{end_block, Fragment};
+use pulldown_cmark::{BrokenLink, CowStr, Event, LinkType, Options, Parser, Tag, TagEnd};
+use std::ops::Range;
+use SourceMap;
+
+/// Extracts prose from a Markdown document.
+///
+/// Code blocks and HTML are skipped while link text and image descriptions are kept.
+/// Inline code and autolinks are replaced by placeholders so that sentences stay whole.
+/// The language can be set with `lang` key of YAML front matter.
+pub fn extract(source: &str) -> Vec<Fragment> {
+    extract_with(source, false)
+}
+
+/// Extracts prose from Markdown of Rust documentation.
+///
+/// Like `extract` but intra-doc links like [`Foo`] or `[Foo::bar][]` are replaced
+/// by placeholders, since their text names an item rather than being prose.
+pub fn extract_rustdoc(source: &str) -> Vec<Fragment> {
+    extract_with(source, true)
+}
+
+fn extract_with(source: &str, intra_doc_links: bool) -> Vec<Fragment> {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+
+    let mut map = SourceMap::new();
+    let mut language = None;
+    // Depth of elements whose text is not prose.
+    let mut skipped = 0;
+    // Whether each of the open links is skipped.
+    let mut links = Vec::new();
+    let mut in_metadata = false;
+
+    // Rustdoc resolves references without a definition as paths to items.
+    let resolve = |link: BrokenLink<'_>| {
+        if intra_doc_links {
+            Some((CowStr::from(link.reference.to_string()), CowStr::from("")))
+        } else {
+            None
+        }
+    };
+    let parser = Parser::new_with_broken_link_callback(source, options, Some(resolve));
+    for (event, range) in parser.into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) | Event::Start(Tag::HtmlBlock) => skipped += 1,
+            Event::End(TagEnd::CodeBlock) | Event::End(TagEnd::HtmlBlock) => {
+                skipped -= 1;
+                end_block(&mut map, range.end);
+            }
+            Event::Start(Tag::Link { link_type, .. }) => {
+                // Autolinks show the URL as their text, intra-doc links the path.
+                let skip = match link_type {
+                    LinkType::Autolink | LinkType::Email => true,
+                    LinkType::ShortcutUnknown | LinkType::CollapsedUnknown => intra_doc_links,
+                    _ => false,
+                };
+                if skip {
+                    if skipped == 0 {
+                        map.push_placeholder(range);
+                    }
+                    skipped += 1;
+                }
+                links.push(skip);
+            }
+            Event::End(TagEnd::Link) if links.pop() == Some(true) => skipped -= 1,
+            Event::Start(Tag::MetadataBlock(_)) => in_metadata = true,
+            Event::End(TagEnd::MetadataBlock(_)) => in_metadata = false,
+            Event::Text(ref text) if in_metadata => {
+                language = front_matter_language(text).or(language);
+            }
+            Event::Text(ref text) if skipped == 0 => push(&mut map, source, text, range),
+            Event::Code(_) if skipped == 0 => map.push_placeholder(range),
+            Event::SoftBreak | Event::HardBreak if skipped == 0 => {
+                push(&mut map, source, "\n", range)
+            }
+            Event::End(
+                TagEnd::Paragraph
+                | TagEnd::Heading(_)
+                | TagEnd::BlockQuote(_)
+                | TagEnd::Item
+                | TagEnd::TableCell
+                | TagEnd::FootnoteDefinition
+                | TagEnd::DefinitionListTitle
+                | TagEnd::DefinitionListDefinition,
+            ) => end_block(&mut map, range.end),
+            _ => {}
+        }
+    }
+
+    vec![Fragment { map, language }]
+}
+
+/// Appends text of an event, which differs from the source when it contains escapes or entities.
+fn push(map: &mut SourceMap, source: &str, text: &str, range: Range<usize>) {
+    if source[range.clone()] == *text {
+        map.push_verbatim(text, range.start);
+    } else {
+        map.push_replacement(text, range);
+    }
+}
+
+/// Finds `lang` (or `language`) key in YAML front matter.
+fn front_matter_language(yaml: &str) -> Option<String> {
+    yaml.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+        if matches!(key.trim(), "lang" | "language") && !value.is_empty() {
+            Some(value.to_string())
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        let source = "---\ntitle: Test\nlang: \"cs\"\n---\n\n# Hello *world*\n\nSee [the docs](https://example.com) or <https://example.org>, use `cargo` &amp; ![a logo](logo.png).\nNext\\* line.\n\n```rust\nfn main() {}\n```\n\n<div>html</div>\n\n- item\n";
+        let fragments = extract(source);
+        assert_eq!(fragments.len(), 1);
+        let fragment = &fragments[0];
+        assert_eq!(fragment.language.as_deref(), Some("cs"));
+        assert_eq!(
+            fragment.map.text(),
+            "Hello world\n\nSee the docs or X, use X & a logo.\nNext* line.\n\nitem\n\n"
+        );
+
+        let text = fragment.map.text();
+        let range = |needle: &str| {
+            let start = text.find(needle).unwrap();
+            fragment.map.to_original(start..start + needle.len())
+        };
+        assert_eq!(&source[range("Hello world")], "Hello *world");
+        assert_eq!(&source[range("the docs")], "the docs");
+        assert_eq!(&source[range("&")], "&amp;");
+        let placeholder = text.find("X,").unwrap();
+        assert!(fragment.map.in_placeholder(&(placeholder..placeholder + 1)));
+        assert_eq!(&source[range("X,")], "<https://example.org>,");
+        assert_eq!(&source[range("use X")], "use `cargo`");
+        assert_eq!(&source[range("Next*")], "Next\\*");
+    }
+
+    #[test]
+    fn test_intra_doc_links() {
+        let source =
+            "Returns [`Foo`] or [Bar::baz] as in [the guide][Guide] and [docs](crate::docs).";
+        let fragments = extract_rustdoc(source);
+        assert_eq!(
+            fragments[0].map.text(),
+            "Returns X or X as in the guide and docs.\n\n"
+        );
+    }
+}
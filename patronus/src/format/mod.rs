@@ -0,0 +1,32 @@
+//! Front-ends extracting prose from documents in various formats.
+//!
+//! Each front-end produces fragments of plain text with maps back to the source document,
+//! to be checked with `Patronus::check_fragments`.
+
+pub mod code;
+pub mod html;
+pub mod latex;
+pub mod markdown;
+pub mod rustdoc;
+pub mod translation;
+
+use SourceMap;
+
+/// Plain text extracted from a part of a document.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Fragment {
+    pub map: SourceMap,
+    /// Language of the fragment when the document specifies it,
+    /// overriding the language the document is checked with.
+    pub language: Option<String>,
+}
+
+/// Ends the current block of text so that it does not run into the next one.
+pub(crate) fn end_block(map: &mut SourceMap, offset: usize) {
+    let text = map.text();
+    if text.is_empty() || text.ends_with("\n\n") {
+        return;
+    }
+    let separator = if text.ends_with('\n') { "\n" } else { "\n\n" };
+    map.push_inserted(separator, offset);
+}
@@ -0,0 +1,220 @@
+//! Documentation comments of Rust sources.
+
+use super::{markdown, Fragment};
+use regex::Regex;
+use std::ops::Range;
+use std::sync::OnceLock;
+use SourceMap;
+
+/// Line of a documentation comment.
+struct DocLine {
+    /// Contents in the source, without the comment marker.
+    range: Range<usize>,
+    /// Contents of a `#[doc]` attribute whose string literal contains escapes.
+    unescaped: Option<String>,
+}
+
+/// Extracts prose from documentation of a Rust source file.
+///
+/// Consecutive `///` or `//!` comments, `/** */` and `/*! */` comments and `#[doc = "…"]`
+/// attributes form documentation blocks, which are read as Markdown with
+/// `markdown::extract_rustdoc`. Code blocks and intra-doc links are skipped.
+pub fn extract(source: &str) -> Vec<Fragment> {
+    let mut fragments = Vec::new();
+    let mut block: Vec<DocLine> = Vec::new();
+    // Whether the current block documents the enclosing item (`//!`).
+    let mut inner = false;
+    let mut lines = lines(source);
+
+    while let Some(line) = lines.next() {
+        let text = &source[line.clone()];
+        let indent = text.len() - text.trim_start().len();
+        let start = line.start + indent;
+        let trimmed = text.trim();
+
+        let (doc_line, line_inner) = if trimmed.starts_with("///") && !trimmed.starts_with("////") {
+            (comment_line(source, start + 3, line.end), false)
+        } else if trimmed.starts_with("//!") {
+            (comment_line(source, start + 3, line.end), true)
+        } else if let Some(captures) = attribute_regex().captures(trimmed) {
+            let contents = captures.get(2).expect("pattern has two groups");
+            let range = start + contents.start()..start + contents.end();
+            let unescaped = if contents.as_str().contains('\\') {
+                Some(unescape(contents.as_str()))
+            } else {
+                None
+            };
+            (DocLine { range, unescaped }, captures.get(1).is_some())
+        } else if (trimmed.starts_with("/**") && !trimmed.starts_with("/***")
+            || trimmed.starts_with("/*!"))
+            && !trimmed.starts_with("/**/")
+        {
+            fragments.extend(markdown_fragments(source, &block));
+            block = block_comment(source, start + 3, line, &mut lines);
+            fragments.extend(markdown_fragments(source, &block));
+            block.clear();
+            continue;
+        } else {
+            fragments.extend(markdown_fragments(source, &block));
+            block.clear();
+            continue;
+        };
+
+        if line_inner != inner {
+            fragments.extend(markdown_fragments(source, &block));
+            block.clear();
+            inner = line_inner;
+        }
+        block.push(doc_line);
+    }
+    fragments.extend(markdown_fragments(source, &block));
+    fragments
+}
+
+/// Lines of the source without line terminators.
+fn lines(source: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut start = 0;
+    source.split('\n').map(move |line| {
+        let range = start..start + line.trim_end_matches('\r').len();
+        start += line.len() + 1;
+        range
+    })
+}
+
+fn comment_line(source: &str, start: usize, end: usize) -> DocLine {
+    let end = start.max(start + source[start..end].trim_end().len());
+    DocLine {
+        range: start..end,
+        unescaped: None,
+    }
+}
+
+/// Collects lines of a block comment whose text starts at `start` on `first` line.
+fn block_comment(
+    source: &str,
+    start: usize,
+    first: Range<usize>,
+    lines: &mut dyn Iterator<Item = Range<usize>>,
+) -> Vec<DocLine> {
+    let mut block = Vec::new();
+    let mut line = start..first.end;
+    loop {
+        let text = &source[line.clone()];
+        let (text, closed) = match text.find("*/") {
+            Some(end) => (&text[..end], true),
+            None => (text, false),
+        };
+        let indent = text.len() - text.trim_start().len();
+        let mut content_start = line.start + indent;
+        // Decoration of the continuation lines.
+        if text[indent..].starts_with('*') && content_start != start {
+            content_start += 1;
+        }
+        block.push(comment_line(source, content_start, line.start + text.len()));
+        if closed {
+            return block;
+        }
+        match lines.next() {
+            Some(next) => line = next,
+            None => return block,
+        }
+    }
+}
+
+/// Reads a documentation block as Markdown and maps the result to the source.
+fn markdown_fragments(source: &str, block: &[DocLine]) -> Vec<Fragment> {
+    if block.iter().all(|line| line.range.is_empty()) {
+        return Vec::new();
+    }
+    // Rustdoc removes the indentation common to all lines.
+    let indent = block
+        .iter()
+        .filter(|line| line.unescaped.is_none())
+        .map(|line| &source[line.range.clone()])
+        .filter(|text| !text.trim().is_empty())
+        .map(|text| text.len() - text.trim_start_matches(' ').len())
+        .min()
+        .unwrap_or(0);
+
+    let mut map = SourceMap::new();
+    for (index, line) in block.iter().enumerate() {
+        if index > 0 {
+            map.push_inserted("\n", line.range.start);
+        }
+        match line.unescaped {
+            Some(ref text) => map.push_replacement(text, line.range.clone()),
+            None => {
+                let start = (line.range.start + indent).min(line.range.end);
+                map.push_verbatim(&source[start..line.range.end], start);
+            }
+        }
+    }
+
+    markdown::extract_rustdoc(map.text())
+        .into_iter()
+        .map(|fragment| Fragment {
+            map: fragment.map.compose(&map),
+            language: fragment.language,
+        })
+        .collect()
+}
+
+/// `#[doc = "…"]` or `#![doc = "…"]` attribute on a single line.
+fn attribute_regex() -> &'static Regex {
+    static ATTRIBUTE: OnceLock<Regex> = OnceLock::new();
+    ATTRIBUTE.get_or_init(|| {
+        Regex::new(r#"^#(!)?\[doc\s*=\s*"((?:[^"\\]|\\.)*)"\s*\]$"#)
+            .expect("attribute pattern is valid")
+    })
+}
+
+/// Decodes common escapes of a string literal.
+fn unescape(literal: &str) -> String {
+    let mut text = String::with_capacity(literal.len());
+    let mut chars = literal.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => text.push('\n'),
+            Some('t') => text.push('\t'),
+            Some('r') => {}
+            Some(c) => text.push(c),
+            None => {}
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        let source = "//! Crate **docs**.\n\n/// Returns [`Foo`] for a\n/// given input.\n///\n/// ```\n/// let x = 1;\n/// ```\n#[doc = \"Attribute \\\"text\\\".\"]\nfn f() {} // not docs\n\n/**\n * Block docs.\n */\nstruct S;\n";
+        let fragments = extract(source);
+        let texts: Vec<&str> = fragments.iter().map(|f| f.map.text()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "Crate docs.\n\n",
+                "Returns X for a\ngiven input.\n\nAttribute \"text\".\n\n",
+                "Block docs.\n\n",
+            ]
+        );
+
+        let text = texts[1];
+        let range = fragments[1].map.to_original(8..9);
+        assert_eq!(&source[range], "[`Foo`]");
+        assert!(fragments[1].map.in_placeholder(&(8..9)));
+        let start = text.find("given").unwrap();
+        let range = fragments[1].map.to_original(start..start + 5);
+        assert_eq!(&source[range], "given");
+        let start = text.find("Attribute").unwrap();
+        let range = fragments[1].map.to_original(start..start + 9);
+        assert_eq!(&source[range], "Attribute \\\"text\\\".");
+    }
+}
@@ -0,0 +1,604 @@
+//! Translation files: gettext PO, XLIFF and Fluent.
+//!
+//! Only translations are extracted, each entry as a separate fragment so that annotations
+//! can be reported per entry. Placeholders like `%s` or `{ $name }` are replaced
+//! by [`PLACEHOLDER`](::PLACEHOLDER) and annotations lying on them are not reported.
+
+use super::{end_block, html, Fragment};
+use ignore::BuiltinPattern;
+use regex::Regex;
+use std::ops::Range;
+use std::sync::OnceLock;
+use {Annotation, SourceMap};
+
+/// Elements of XLIFF inline markup.
+const XLIFF_VOCABULARY: html::Vocabulary = html::Vocabulary {
+    // Native codes of formatting.
+    skipped: &["bpt", "ept", "it"],
+    inline: &["bpt", "bx", "ec", "ept", "ex", "g", "it", "mrk", "pc", "sc"],
+    // Placeholders standing for content, like variables.
+    placeholders: &["ph", "x"],
+};
+
+/// Translated message of a translation file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// Identifier of the message: `msgid` in PO files, preceded by `msgctxt` and `\u{4}`
+    /// like gettext does when the message has a context, `id` of an XLIFF unit,
+    /// or Fluent message (or attribute) name.
+    pub id: String,
+    pub fragment: Fragment,
+}
+
+impl Entry {
+    /// Line and column, both starting at 1, where the translation starts in the source.
+    pub fn location(&self, source: &str) -> (usize, usize) {
+        line_column(source, self.fragment.map.start_to_original(0))
+    }
+}
+
+/// Annotation of a translation entry, see `Patronus::check_translations`.
+#[derive(Clone, Debug)]
+pub struct EntryAnnotation {
+    /// Identifier of the entry, see [`Entry::id`].
+    pub id: String,
+    /// Line of the annotation in the file, starting at 1.
+    pub line: usize,
+    /// Column of the annotation in characters, starting at 1.
+    pub column: usize,
+    pub annotation: Annotation,
+}
+
+/// Returns the line and column, both starting at 1, of a byte offset in the source.
+pub fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let line_start = source[..offset]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    (
+        source[..line_start].matches('\n').count() + 1,
+        source[line_start..offset].chars().count() + 1,
+    )
+}
+
+/// PO entry being read.
+#[derive(Default)]
+struct PoEntry {
+    context: Option<String>,
+    msgid: String,
+    fuzzy: bool,
+    /// Whether a `msgstr` was read, so that the next `msgctxt` or `msgid` starts a new entry.
+    translated: bool,
+}
+
+/// Extracts `msgstr` translations of a gettext PO file.
+///
+/// The language is taken from the `Language` field of the header entry.
+/// Untranslated, fuzzy and obsolete (`#~`) entries are skipped.
+pub fn extract_po(source: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut language = None;
+    let mut entry = PoEntry::default();
+    // Keyword whose string literals are being read and their contents.
+    let mut keyword = String::new();
+    let mut literals: Vec<Range<usize>> = Vec::new();
+
+    let mut finish = |keyword: &str, literals: &mut Vec<Range<usize>>, entry: &mut PoEntry| {
+        let literals = std::mem::take(literals);
+        let unescaped = || {
+            literals
+                .iter()
+                .map(|literal| unescape(&source[literal.clone()]))
+                .collect()
+        };
+        if keyword == "msgctxt" {
+            entry.context = Some(unescaped());
+        } else if keyword == "msgid" {
+            entry.msgid = unescaped();
+        } else if keyword.starts_with("msgstr") {
+            entry.translated = true;
+            let mut map = SourceMap::new();
+            for literal in &literals {
+                push_escaped(&mut map, source, literal.clone());
+            }
+            if entry.msgid.is_empty() && entry.context.is_none() {
+                language = header_language(map.text()).or(language.take());
+            } else if !entry.fuzzy && !map.text().is_empty() {
+                let mut id = match entry.context {
+                    Some(ref context) => format!("{}\u{4}{}", context, entry.msgid),
+                    None => entry.msgid.clone(),
+                };
+                if let Some(index) = keyword.strip_prefix("msgstr") {
+                    id.push_str(index);
+                }
+                entries.push(Entry {
+                    id,
+                    fragment: Fragment {
+                        map: replace_placeholders(map),
+                        language: None,
+                    },
+                });
+            }
+        }
+    };
+
+    for line in lines(source) {
+        let text = &source[line.clone()];
+        let trimmed = text.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            finish(&keyword, &mut literals, &mut entry);
+            keyword.clear();
+            if trimmed.is_empty() || entry.translated {
+                entry = PoEntry::default();
+            }
+            if let Some(flags) = trimmed.strip_prefix("#,") {
+                entry.fuzzy |= flags.split(',').any(|flag| flag.trim() == "fuzzy");
+            }
+            continue;
+        }
+        let indent = text.len() - text.trim_start().len();
+        let literal_start = match trimmed.find('"') {
+            Some(quote) => line.start + indent + quote,
+            None => continue,
+        };
+        if !trimmed.starts_with('"') {
+            finish(&keyword, &mut literals, &mut entry);
+            keyword = trimmed[..trimmed.find(char::is_whitespace).unwrap_or(0)].to_string();
+            if entry.translated && (keyword == "msgctxt" || keyword == "msgid") {
+                entry = PoEntry::default();
+            }
+        }
+        let end = literal_end(source, literal_start + 1, line.end);
+        literals.push(literal_start + 1..end);
+    }
+    finish(&keyword, &mut literals, &mut entry);
+
+    for entry in &mut entries {
+        entry.fragment.language = language.clone();
+    }
+    entries
+}
+
+/// Extracts `target` elements of an XLIFF 1.2 or 2.0 file.
+///
+/// The language is taken from `target-language` (or `trgLang`) attribute,
+/// or from `xml:lang` of the target itself.
+pub fn extract_xliff(source: &str) -> Vec<Entry> {
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    let tag_regex = TAG.get_or_init(|| {
+        Regex::new(r"<(/?)((?:[\w.-]+:)?)(xliff|file|trans-unit|unit|target)\b([^>]*?)(/?)>")
+            .expect("XLIFF pattern is valid")
+    });
+
+    let mut entries = Vec::new();
+    let mut language = None;
+    let mut id = String::new();
+    let mut position = 0;
+    while let Some(captures) = tag_regex.captures_at(source, position) {
+        let tag = captures.get(0).expect("match has a whole-match group");
+        position = tag.end();
+        let attributes = &captures[4];
+        match (&captures[1], &captures[3]) {
+            ("/", "trans-unit") | ("/", "unit") => id.clear(),
+            ("/", _) => {}
+            (_, "xliff") | (_, "file") => {
+                if let Some(target_language) = attribute(attributes, "target-language")
+                    .or_else(|| attribute(attributes, "trgLang"))
+                {
+                    language = Some(target_language);
+                }
+            }
+            (_, "trans-unit") | (_, "unit") => {
+                id = attribute(attributes, "id").unwrap_or_default();
+            }
+            // An empty target has no translation.
+            _ if &captures[5] == "/" => {}
+            _ => {
+                let closing = format!("</{}target>", &captures[2]);
+                let end = source[position..]
+                    .find(&closing)
+                    .map_or(source.len(), |end| position + end);
+                let contents = &source[position..end];
+                let target_language = attribute(attributes, "xml:lang").or(language.clone());
+
+                // Offsets of the extracted text are relative to the target contents.
+                let mut base = SourceMap::new();
+                base.push_verbatim(contents, position);
+                let fragments = html::extract_xml_with(contents, XLIFF_VOCABULARY);
+                for fragment in fragments {
+                    if fragment.map.text().trim().is_empty() {
+                        continue;
+                    }
+                    entries.push(Entry {
+                        id: id.clone(),
+                        fragment: Fragment {
+                            map: replace_placeholders(fragment.map.compose(&base)),
+                            language: fragment.language.or(target_language.clone()),
+                        },
+                    });
+                }
+                position = (end + closing.len()).min(source.len());
+            }
+        }
+    }
+    entries
+}
+
+/// Finds the value of an attribute in the attribute list of a tag.
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    static ATTRIBUTE: OnceLock<Regex> = OnceLock::new();
+    let attribute_regex = ATTRIBUTE.get_or_init(|| {
+        Regex::new(r#"([\w:.-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#)
+            .expect("attribute pattern is valid")
+    });
+    attribute_regex
+        .captures_iter(attributes)
+        .find(|captures| &captures[1] == name)
+        .and_then(|captures| captures.get(2).or_else(|| captures.get(3)))
+        .map(|value| html::decode(value.as_str()))
+}
+
+/// Replaces placeholders of translation strings, like `%s` or `{ $name }`,
+/// by a placeholder word.
+fn replace_placeholders(map: SourceMap) -> SourceMap {
+    let text = map.text();
+    let placeholders = BuiltinPattern::Placeholder.find(text);
+    if placeholders.is_empty() {
+        return map;
+    }
+    let mut replaced = SourceMap::new();
+    let mut position = 0;
+    for placeholder in placeholders {
+        replaced.push_verbatim(&text[position..placeholder.start], position);
+        replaced.push_placeholder(placeholder.clone());
+        position = placeholder.end;
+    }
+    replaced.push_verbatim(&text[position..], position);
+    replaced.compose(&map)
+}
+
+/// Extracts messages, terms and attributes of a Fluent (`.ftl`) file.
+///
+/// Variants of select expressions become separate blocks of the message.
+pub fn extract_fluent(source: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    // Name of the current message and its value being read.
+    let mut message = String::new();
+    let mut value: Option<(String, Range<usize>)> = None;
+
+    let finish = |value: &mut Option<(String, Range<usize>)>, entries: &mut Vec<Entry>| {
+        if let Some((id, range)) = value.take() {
+            let mut map = SourceMap::new();
+            push_pattern(&mut map, source, range);
+            if !map.text().trim().is_empty() {
+                entries.push(Entry {
+                    id,
+                    fragment: Fragment {
+                        map: replace_placeholders(map),
+                        language: None,
+                    },
+                });
+            }
+        }
+    };
+
+    for line in lines(source) {
+        let text = &source[line.clone()];
+        if text.trim().is_empty() || text.starts_with('#') {
+            finish(&mut value, &mut entries);
+            continue;
+        }
+        let indented = text.starts_with(char::is_whitespace);
+        let trimmed = text.trim_start();
+        if indented && !trimmed.starts_with('.') || trimmed.starts_with('}') {
+            // Continuation of a multiline value, the closing brace need not be indented.
+            if let Some((_, ref mut range)) = value {
+                range.end = line.end;
+            }
+            continue;
+        }
+        finish(&mut value, &mut entries);
+        let equals = match text.find('=') {
+            Some(equals) => equals,
+            None => continue,
+        };
+        let name = text[..equals].trim();
+        let id = if indented {
+            format!("{}{}", message, name)
+        } else {
+            message = name.to_string();
+            message.clone()
+        };
+        let start = line.start + equals + 1;
+        let start =
+            start + (source[start..line.end].len() - source[start..line.end].trim_start().len());
+        value = Some((id, start..line.end));
+    }
+    finish(&mut value, &mut entries);
+    entries
+}
+
+/// Appends a Fluent pattern, keeping simple placeables and splitting select expressions.
+fn push_pattern(map: &mut SourceMap, source: &str, range: Range<usize>) {
+    let mut position = range.start;
+    let mut verbatim = position;
+    while position < range.end {
+        let c = source[position..]
+            .chars()
+            .next()
+            .expect("position is inside range");
+        match c {
+            '\n' => {
+                map.push_verbatim(&source[verbatim..position + 1], verbatim);
+                position += 1;
+                // Indentation of continuation lines is not part of the text.
+                position += source[position..range.end].len()
+                    - source[position..range.end]
+                        .trim_start_matches([' ', '\t'])
+                        .len();
+                verbatim = position;
+            }
+            '{' => {
+                let end = placeable_end(source, position, range.end);
+                let placeable = &source[position..end];
+                if let Some(arrow) = selector_arrow(placeable) {
+                    map.push_verbatim(&source[verbatim..position], verbatim);
+                    end_block(map, position);
+                    push_variants(map, source, position + arrow + 2..end - 1);
+                    verbatim = end;
+                }
+                position = end;
+            }
+            c => position += c.len_utf8(),
+        }
+    }
+    map.push_verbatim(&source[verbatim..range.end], verbatim);
+}
+
+/// Appends each variant of a select expression as a separate block.
+fn push_variants(map: &mut SourceMap, source: &str, range: Range<usize>) {
+    let mut variants: Vec<Range<usize>> = Vec::new();
+    for line in lines(source).filter(|line| line.start < range.end && range.start < line.end) {
+        let line = line.start.max(range.start)..line.end.min(range.end);
+        let text = &source[line.clone()];
+        let trimmed = text.trim_start().trim_start_matches('*');
+        if trimmed.starts_with('[') {
+            let key_end = trimmed.find(']').map_or(trimmed.len(), |end| end + 1);
+            let start = line.end - trimmed.len() + key_end;
+            let start = start
+                + (source[start..line.end].len() - source[start..line.end].trim_start().len());
+            variants.push(start..line.end);
+        } else if let Some(variant) = variants.last_mut() {
+            variant.end = line.end;
+        }
+    }
+    for variant in variants {
+        let end = variant.start + source[variant.clone()].trim_end().len();
+        push_pattern(map, source, variant.start..end);
+        end_block(map, end);
+    }
+}
+
+/// Returns the position after the placeable starting at `position`.
+fn placeable_end(source: &str, position: usize, limit: usize) -> usize {
+    let mut depth = 0;
+    let mut in_string = false;
+    for (offset, c) in source[position..limit].char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    return position + offset + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    limit
+}
+
+/// Finds `->` of a select expression outside of nested placeables.
+fn selector_arrow(placeable: &str) -> Option<usize> {
+    let nested = placeable[1..]
+        .find('{')
+        .map_or(placeable.len(), |start| start + 1);
+    placeable[..nested].find("->")
+}
+
+/// Lines of the source without line terminators.
+fn lines(source: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut start = 0;
+    source.split('\n').map(move |line| {
+        let range = start..start + line.trim_end_matches('\r').len();
+        start += line.len() + 1;
+        range
+    })
+}
+
+/// Finds the closing quote of a string literal whose contents start at `start`.
+fn literal_end(source: &str, start: usize, limit: usize) -> usize {
+    let mut escaped = false;
+    for (offset, c) in source[start..limit].char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return start + offset,
+            _ => {}
+        }
+    }
+    limit
+}
+
+/// Appends contents of a C-like string literal, replacing escape sequences.
+fn push_escaped(map: &mut SourceMap, source: &str, range: Range<usize>) {
+    let mut verbatim = range.start;
+    let mut index = range.start;
+    while index < range.end {
+        if !source[index..].starts_with('\\') {
+            index += source[index..].chars().next().map_or(1, char::len_utf8);
+            continue;
+        }
+        map.push_verbatim(&source[verbatim..index], verbatim);
+        let end = (index + 2).min(range.end);
+        map.push_replacement(&unescape(&source[index..end]), index..end);
+        index = end;
+        verbatim = index;
+    }
+    map.push_verbatim(&source[verbatim..range.end], verbatim);
+}
+
+fn unescape(literal: &str) -> String {
+    let mut text = String::with_capacity(literal.len());
+    let mut chars = literal.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => text.push('\n'),
+            Some('t') => text.push('\t'),
+            Some(c) => text.push(c),
+            None => {}
+        }
+    }
+    text
+}
+
+/// Reads `Language` field of a PO header.
+fn header_language(header: &str) -> Option<String> {
+    header.lines().find_map(|line| {
+        let value = line.strip_prefix("Language:")?.trim();
+        if value.is_empty() {
+            None
+        } else {
+            Some(value.to_string())
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tests;
+    use {AnnotationKind, Properties};
+
+    fn original<'a>(source: &'a str, entry: &Entry, needle: &str) -> &'a str {
+        let start = entry.fragment.map.text().find(needle).unwrap();
+        &source[entry.fragment.map.to_original(start..start + needle.len())]
+    }
+
+    #[test]
+    fn test_po() {
+        let source = r#"msgid ""
+msgstr ""
+"Language: cs\n"
+"Content-Type: text/plain; charset=UTF-8\n"
+
+#: src/main.c:10
+msgid "Deleted %s files"
+msgstr "Smazáno %s "
+"souborů"
+
+msgid "One file"
+msgid_plural "%d files"
+msgstr[0] "Jeden \"soubor\""
+msgstr[1] ""
+
+#, fuzzy
+msgid "Draft"
+msgstr "Koncept"
+
+msgctxt "menu"
+msgid "File"
+msgstr "Soubor"
+
+#~ msgid "Old"
+#~ msgstr "Starý"
+"#;
+        let entries = extract_po(source);
+        let summary: Vec<(&str, &str)> = entries
+            .iter()
+            .map(|entry| (entry.id.as_str(), entry.fragment.map.text()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("Deleted %s files", "Smazáno X souborů"),
+                ("One file[0]", "Jeden \"soubor\""),
+                ("menu\u{4}File", "Soubor"),
+            ]
+        );
+        assert_eq!(entries[0].fragment.language.as_deref(), Some("cs"));
+        assert_eq!(original(source, &entries[0], "souborů"), "souborů");
+        assert_eq!(original(source, &entries[1], "\"soubor"), "\\\"soubor");
+        assert_eq!(original(source, &entries[0], "X"), "%s");
+        assert!(entries[0].fragment.map.in_placeholder(&(9..10)));
+        assert_eq!(entries[0].location(source), (8, 9));
+        assert_eq!(line_column(source, source.find("Jeden").unwrap()), (13, 12));
+    }
+
+    #[test]
+    fn test_check_translations() {
+        let patronus =
+            tests::patronus(vec![tests::provider(0, &["teh"], AnnotationKind::Spelling)]);
+        let source = "msgid \"a\"\nmsgstr \"Jeden teh\"\n\n# patronus-disable\nmsgid \"b\"\nmsgstr \"Dva teh\"\n# patronus-enable\n";
+        let props = Properties {
+            primary_language: "cs".to_string(),
+        };
+        let annotations = patronus.check_translations(&props, source, &extract_po(source));
+        let positions: Vec<(&str, usize, usize)> = annotations
+            .iter()
+            .map(|ann| (ann.id.as_str(), ann.line, ann.column))
+            .collect();
+        assert_eq!(positions, vec![("a", 2, 15)]);
+        assert_eq!(
+            annotations[0].annotation.offset,
+            source.find("teh").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_xliff_and_fluent() {
+        let source = r#"<xliff version="1.2"><file source-language="en" target-language="de">
+<trans-unit id="greeting"><source>Hello</source><target>Hallo <g id="1">Welt</g> &amp; mehr</target></trans-unit>
+</file></xliff>"#;
+        let entries = extract_xliff(source);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].id, "greeting");
+        assert_eq!(entries[0].fragment.map.text(), "Hallo Welt & mehr");
+        assert_eq!(entries[0].fragment.language.as_deref(), Some("de"));
+        assert_eq!(original(source, &entries[0], "Welt"), "Welt");
+
+        // XLIFF 2.0 with namespaces, self-closing targets and placeholder markup.
+        let source = r#"<x:xliff version="2.0" srcLang="en" trgLang="fr"><x:file id="f">
+<x:unit id="empty"><x:segment><x:source>Empty</x:source><x:target/></x:segment></x:unit>
+<x:unit id="a &amp; b"><x:segment><x:source>Open</x:source><x:target xml:lang="fr-CA">Ouvrir <x:ph id="1"/> le <x:pc id="2">fichier</x:pc></x:target></x:segment></x:unit>
+</x:file></x:xliff>"#;
+        let entries = extract_xliff(source);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].id, "a & b");
+        assert_eq!(entries[0].fragment.map.text(), "Ouvrir X le fichier");
+        assert_eq!(original(source, &entries[0], "X"), r#"<x:ph id="1"/>"#);
+        assert_eq!(entries[0].fragment.language.as_deref(), Some("fr-CA"));
+        assert_eq!(original(source, &entries[0], "fichier"), "fichier");
+
+        let source = "# Comment\nhello = Hello, { $user }!\n    How are you?\n    .title = Greeting\nfiles = { $count ->\n    [one] One file\n   *[other] { $count } files\n}\n-brand = Firefox\n";
+        let entries = extract_fluent(source);
+        let summary: Vec<(&str, &str)> = entries
+            .iter()
+            .map(|entry| (entry.id.as_str(), entry.fragment.map.text()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("hello", "Hello, X!\nHow are you?"),
+                ("hello.title", "Greeting"),
+                ("files", "One file\n\nX files\n\n"),
+                ("-brand", "Firefox"),
+            ]
+        );
+        assert_eq!(original(source, &entries[0], "How"), "How");
+    }
+}
@@ -0,0 +1,306 @@
+use error::Error;
+use regex::Regex;
+use std::borrow::Cow;
+use std::ops::Range;
+use std::sync::OnceLock;
+use Annotation;
+
+/// Kinds of text that are not prose, recognized by Patronus out of the box.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BuiltinPattern {
+    /// Web addresses like `https://example.com/path`.
+    Url,
+    /// E-mail addresses.
+    Email,
+    /// File system paths like `/usr/lib`, `~/.config` or `C:\Windows`.
+    Path,
+    /// Numbers with units like `10ms`, `4 GiB` or `25 °C`.
+    Quantity,
+    /// Hexadecimal hashes and identifiers like `b362d2e`.
+    Hash,
+    /// Code spans delimited by backticks.
+    CodeSpan,
+    /// Placeholders of translation strings like `%s`, `%(name)d`, `{0}` or `{ $name }`.
+    /// The translation front-ends replace them on their own.
+    Placeholder,
+}
+
+impl BuiltinPattern {
+    /// Patterns suitable for any prose. `Placeholder` is left out,
+    /// since braces and percent signs are common outside of translation strings.
+    pub const ALL: [BuiltinPattern; 6] = [
+        BuiltinPattern::Url,
+        BuiltinPattern::Email,
+        BuiltinPattern::Path,
+        BuiltinPattern::Quantity,
+        BuiltinPattern::Hash,
+        BuiltinPattern::CodeSpan,
+    ];
+
+    fn regex(self) -> &'static Regex {
+        static URL: OnceLock<Regex> = OnceLock::new();
+        static EMAIL: OnceLock<Regex> = OnceLock::new();
+        static PATH: OnceLock<Regex> = OnceLock::new();
+        static QUANTITY: OnceLock<Regex> = OnceLock::new();
+        static HASH: OnceLock<Regex> = OnceLock::new();
+        static CODE_SPAN: OnceLock<Regex> = OnceLock::new();
+        static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+
+        let (cell, pattern) = match self {
+            BuiltinPattern::Url => (
+                &URL,
+                r#"\b(?:[a-zA-Z][a-zA-Z0-9+.-]*://|www\.)[^\s<>"]*[^\s<>".,;:!?'()\[\]]"#,
+            ),
+            BuiltinPattern::Email => (
+                &EMAIL,
+                r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}\b",
+            ),
+            // The leading character is not part of the match, see `find`.
+            BuiltinPattern::Path => (
+                &PATH,
+                r#"(?:^|[\s(\["'])((?:~|\.\.?)?/[\w.@+-]+(?:/[\w.@+-]*)*|[A-Za-z]:\\[^\s"'<>|]+)"#,
+            ),
+            BuiltinPattern::Quantity => (
+                &QUANTITY,
+                r"\b\d+(?:[.,]\d+)*(?:[A-Za-zµ°%]+\b|%|\s(?:(?:[kMGTPmµn]?(?:B|iB|b|bit|Hz|m|g|s|V|W|A|L|l)|px|em|pt)\b|%|°[CF]))",
+            ),
+            BuiltinPattern::Hash => (&HASH, r"\b[0-9a-fA-F]{7,}\b"),
+            BuiltinPattern::CodeSpan => (&CODE_SPAN, r"`[^`\n]+`"),
+            BuiltinPattern::Placeholder => (
+                &PLACEHOLDER,
+                r"%(?:\d+\$)?[-+0#]*(?:\d+|\*)?(?:\.(?:\d+|\*))?(?:hh|h|ll|l|L|z|j|t)?[diouxXeEfFgGaAcsp]\b|%\([A-Za-z_]\w*\)[-+0#]*\d*(?:\.\d+)?[diouxXeEfFgGcrsa]\b|\{[^{}\n]*\}",
+            ),
+        };
+        cell.get_or_init(|| Regex::new(pattern).expect("invalid built-in pattern"))
+    }
+
+    pub(crate) fn find(self, text: &str) -> Vec<Range<usize>> {
+        self.regex()
+            .captures_iter(text)
+            .map(|captures| {
+                captures
+                    .get(1)
+                    .or_else(|| captures.get(0))
+                    .expect("match has a whole-match group")
+            })
+            .filter(|found| match self {
+                // Words consisting of letters a–f are common, require a digit.
+                BuiltinPattern::Hash => found.as_str().contains(|c: char| c.is_ascii_digit()),
+                // Ordinals like `3rd` are words of the sentence, not quantities.
+                BuiltinPattern::Quantity => !is_ordinal(found.as_str()),
+                _ => true,
+            })
+            .map(|found| found.range())
+            .collect()
+    }
+}
+
+fn is_ordinal(text: &str) -> bool {
+    let suffix = text.trim_start_matches(|c: char| c.is_ascii_digit());
+    ["st", "nd", "rd", "th"]
+        .iter()
+        .any(|ordinal| suffix.eq_ignore_ascii_case(ordinal))
+}
+
+/// Patterns of text that should not be checked.
+///
+/// Matching parts of a text are replaced by spaces before it is passed to providers
+/// and annotations overlapping them are discarded.
+///
+/// Nothing is ignored by default, built-in patterns have to be enabled explicitly
+/// because they can hide real mistakes in prose.
+#[derive(Clone, Debug, Default)]
+pub struct IgnorePatterns {
+    pub builtins: Vec<BuiltinPattern>,
+    pub custom: Vec<Regex>,
+}
+
+impl IgnorePatterns {
+    /// Patterns ignoring nothing, same as `default`.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Patterns ignoring all the built-in kinds of text.
+    pub fn all() -> Self {
+        Self {
+            builtins: BuiltinPattern::ALL.to_vec(),
+            custom: Vec::new(),
+        }
+    }
+
+    /// Adds a custom regular expression.
+    pub fn add_regex(&mut self, pattern: &str) -> Result<(), Error> {
+        let regex = Regex::new(pattern).map_err(|source| Error::InvalidPattern { source })?;
+        self.custom.push(regex);
+        Ok(())
+    }
+
+    /// Finds the ranges to be ignored, sorted and with overlapping ranges joined.
+    pub fn find(&self, text: &str) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = self
+            .builtins
+            .iter()
+            .flat_map(|builtin| builtin.find(text))
+            .chain(
+                self.custom
+                    .iter()
+                    .flat_map(|regex| regex.find_iter(text).map(|found| found.range())),
+            )
+            .filter(|range| !range.is_empty())
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+
+        let mut joined: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match joined.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => joined.push(range),
+            }
+        }
+        joined
+    }
+
+    /// Replaces the ignored parts of the text with spaces, keeping the byte offsets intact.
+    /// Returns the masked text and the masked ranges.
+    pub fn mask<'a>(&self, text: &'a str) -> (Cow<'a, str>, Vec<Range<usize>>) {
+        let ranges = self.find(text);
+        if ranges.is_empty() {
+            return (Cow::Borrowed(text), ranges);
+        }
+
+        let mut masked = String::with_capacity(text.len());
+        let mut position = 0;
+        for range in &ranges {
+            masked.push_str(&text[position..range.start]);
+            masked.extend(std::iter::repeat_n(' ', range.len()));
+            position = range.end;
+        }
+        masked.push_str(&text[position..]);
+        (Cow::Owned(masked), ranges)
+    }
+}
+
+/// Discards annotations overlapping any of the masked ranges.
+pub fn filter_masked(annotations: Vec<Annotation>, masked: &[Range<usize>]) -> Vec<Annotation> {
+    if masked.is_empty() {
+        return annotations;
+    }
+    annotations
+        .into_iter()
+        .filter(|ann| {
+            let end = ann.offset + ann.length;
+            !masked
+                .iter()
+                .any(|range| ann.offset < range.end && range.start < end.max(ann.offset + 1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tests;
+    use AnnotationKind;
+
+    fn found(patterns: &IgnorePatterns, text: &str) -> Vec<String> {
+        patterns
+            .find(text)
+            .into_iter()
+            .map(|range| text[range].to_string())
+            .collect()
+    }
+
+    fn builtin(pattern: BuiltinPattern) -> IgnorePatterns {
+        IgnorePatterns {
+            builtins: vec![pattern],
+            custom: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_builtins() {
+        assert_eq!(
+            found(
+                &builtin(BuiltinPattern::Url),
+                "See https://example.com/a_(b)?x=1. Or www.example.org, ok?"
+            ),
+            vec!["https://example.com/a_(b)?x=1", "www.example.org"]
+        );
+        assert_eq!(
+            found(
+                &builtin(BuiltinPattern::Email),
+                "Mail jan.novak@mail.example.cz."
+            ),
+            vec!["jan.novak@mail.example.cz"]
+        );
+        assert_eq!(
+            found(
+                &builtin(BuiltinPattern::Path),
+                "Edit ~/.config/patronus/config.toml and/or (/usr/lib), C:\\Windows"
+            ),
+            vec!["~/.config/patronus/config.toml", "/usr/lib", "C:\\Windows"]
+        );
+        assert_eq!(
+            found(
+                &builtin(BuiltinPattern::Quantity),
+                "It takes 10ms, 4 GiB, 50 % and 3 cats for the 3rd or 21ST time."
+            ),
+            vec!["10ms", "4 GiB", "50 %"]
+        );
+        assert_eq!(
+            found(
+                &builtin(BuiltinPattern::Hash),
+                "Commit b362d2e was defaced."
+            ),
+            vec!["b362d2e"]
+        );
+        assert_eq!(
+            found(
+                &builtin(BuiltinPattern::CodeSpan),
+                "Call `Patronus::check`."
+            ),
+            vec!["`Patronus::check`"]
+        );
+        assert_eq!(
+            found(
+                &builtin(BuiltinPattern::Placeholder),
+                "Deleted %s of %(count)d files in {0} for { $user }, 100% sure."
+            ),
+            vec!["%s", "%(count)d", "{0}", "{ $user }"]
+        );
+    }
+
+    #[test]
+    fn test_default() {
+        let text = "See https://example.com in 10ms.";
+        assert!(IgnorePatterns::default().find(text).is_empty());
+        assert_eq!(
+            found(&IgnorePatterns::all(), text),
+            vec!["https://example.com", "10ms"]
+        );
+    }
+
+    #[test]
+    fn test_mask_and_filter() {
+        let mut patterns = IgnorePatterns::none();
+        patterns.add_regex(r"JIRA-\d+").unwrap();
+        let text = "Fixd in JIRA-123 and JIRA-4.";
+        let (masked, ranges) = patterns.mask(text);
+        assert_eq!(masked, "Fixd in          and       .");
+        assert_eq!(ranges, vec![8..16, 21..27]);
+
+        let mut patronus = tests::patronus(vec![tests::provider(
+            0,
+            &["Fixd", "JIRA"],
+            AnnotationKind::Spelling,
+        )]);
+        patronus.ignore = patterns.clone();
+        assert_eq!(
+            tests::check(&patronus, text),
+            vec![(0, "First".to_string())]
+        );
+
+        assert!(patterns.add_regex("(").is_err());
+    }
+}
@@ -1,34 +1,140 @@
+#[cfg(unix)]
+extern crate libc;
 extern crate libloading as lib;
 extern crate patronus_provider;
+extern crate pulldown_cmark;
+extern crate regex;
+#[macro_use]
+extern crate serde;
+extern crate serde_json;
+extern crate sha2;
+extern crate tempfile;
+extern crate toml;
+extern crate xdg;
 
-use self::error::Error;
+pub use self::baseline::Baseline;
+pub use self::cache::Cache;
+pub use self::consensus::{apply_consensus, Quorum};
+pub use self::dictionary::{filter_known, Dictionary};
+pub use self::document::Document;
+pub use self::edit::{apply, auto_fix, rebase, TextEdit};
+pub use self::error::Error;
+use self::format::translation;
+pub use self::format::Fragment;
+pub use self::ignore::{filter_masked, BuiltinPattern, IgnorePatterns};
+pub use self::merge::{merge, MergeOptions};
+pub use self::policy::{LoadDiagnostic, LoadPolicy, Rejection};
+pub use self::rules::{filter_disabled, Rule, RuleSettings};
+pub use self::source_map::{SourceMap, PLACEHOLDER};
+pub use self::suppress::Suppressions;
 use patronus_provider as provider;
-pub use patronus_provider::AnnotationKind;
+pub use patronus_provider::{AnnotationKind, Severity};
 use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::ffi::CStr;
 use std::ffi::CString;
 use std::fs;
-use std::os::raw::c_int;
+use std::mem;
+use std::ops::Range;
+use std::os::raw::{c_char, c_int};
 use std::path::Path;
 use std::path::PathBuf;
+use std::ptr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 
+/// Reads a field of a structure allocated by a provider,
+/// `None` when the provider’s version of the structure of `size` bytes does not have it.
+macro_rules! field {
+    ($ptr:expr, $size:expr, $type:ident, $field:ident) => {
+        read_field(
+            $ptr as *const u8,
+            $size,
+            mem::offset_of!(provider::$type, $field),
+        )
+    };
+}
+
+mod baseline;
+mod cache;
+mod chunk;
+mod consensus;
+mod dictionary;
+mod document;
+mod edit;
 mod error;
+pub mod format;
+mod ignore;
+mod merge;
+mod policy;
+mod rules;
+mod source_map;
+mod suppress;
 
 /// Represents a profile to be passed down to checkers.
 /// Currently only primary language is supported.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Properties {
     pub primary_language: String,
 }
 
+/// Suggested fix for an annotated issue.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Suggestion {
+    /// Text to put in place of the range, empty for deletion.
+    pub replacement: String,
+    /// Short explanation, e.g. “British spelling”.
+    pub description: Option<String>,
+    /// Confidence of the provider between 0 and 1.
+    pub confidence: Option<f32>,
+    /// Range to replace when it differs from the annotation’s range.
+    pub range: Option<Range<usize>>,
+}
+
+impl Suggestion {
+    /// Range of the text replaced by the suggestion.
+    pub fn range(&self, annotation: &Annotation) -> Range<usize> {
+        self.range
+            .clone()
+            .unwrap_or(annotation.offset..annotation.offset + annotation.length)
+    }
+}
+
 /// Unified annotation produced by the checkers.
-#[derive(Debug)]
+/// Offset and length are measured in bytes of the checked text.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Annotation {
     pub offset: usize,
     pub length: usize,
     pub message: String,
     pub kind: AnnotationKind,
-    pub suggestions: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+    /// Name of the provider that produced the annotation.
+    pub provider: String,
+    /// Names of all providers that reported the issue, when annotations were merged.
+    pub contributors: Vec<String>,
+    /// Stable identifier of the rule that fired, if the provider reports it.
+    pub rule_id: Option<String>,
+    /// Category of the rule.
+    pub category: Option<String>,
+    /// URL with more information about the issue.
+    pub url: Option<String>,
+    pub severity: Severity,
+    /// Confidence of the provider between 0 and 1.
+    pub confidence: Option<f32>,
+}
+
+impl Annotation {
+    /// Moves the annotation further into the text,
+    /// e.g. when it was produced for a part of a bigger text.
+    pub(crate) fn shift(&mut self, distance: usize) {
+        self.offset += distance;
+        for suggestion in &mut self.suggestions {
+            if let Some(ref mut range) = suggestion.range {
+                *range = range.start + distance..range.end + distance;
+            }
+        }
+    }
 }
 
 const PROVIDER_VERSION_FUNCTION: &[u8] = b"patronus_provider_version\0";
@@ -39,58 +145,399 @@ const PROVIDER_INIT_FUNCTION: &[u8] = b"patronus_provider_init\0";
 pub struct Provider {
     internal: *mut provider::Provider,
     library: *mut lib::Library,
+    /// Version of the provider interface the provider implements.
+    version: c_int,
+    /// Optional capabilities of the provider.
+    extensions: Extensions,
+    /// Describes the library the provider was loaded from.
+    library_identity: String,
 }
 
+/// Fields of `provider::Provider` that follow `struct_size`,
+/// with defaults for providers that do not have them.
+struct Extensions {
+    fingerprint: Option<provider::FingerprintFn>,
+    max_text_length: usize,
+    thread_safe: bool,
+    add_word: Option<provider::WordFn>,
+    remove_word: Option<provider::WordFn>,
+    ignore_word: Option<provider::WordFn>,
+    list_rules: Option<provider::ListRulesFn>,
+    free_rules: Option<provider::FreeRulesFn>,
+}
+
+impl Extensions {
+    unsafe fn read(internal: *const provider::Provider, version: c_int) -> Self {
+        // Providers of version 1 end right before `struct_size`.
+        let size = if version == 1 {
+            0
+        } else {
+            ptr::read_unaligned(ptr::addr_of!((*internal).struct_size))
+        };
+        Self {
+            fingerprint: field!(internal, size, Provider, fingerprint).flatten(),
+            max_text_length: field!(internal, size, Provider, max_text_length).unwrap_or(0),
+            thread_safe: field!(internal, size, Provider, thread_safe).unwrap_or(false),
+            add_word: field!(internal, size, Provider, add_word).flatten(),
+            remove_word: field!(internal, size, Provider, remove_word).flatten(),
+            ignore_word: field!(internal, size, Provider, ignore_word).flatten(),
+            list_rules: field!(internal, size, Provider, list_rules).flatten(),
+            free_rules: field!(internal, size, Provider, free_rules).flatten(),
+        }
+    }
+}
+
+/// Provider and properties shared by threads checking chunks of a text.
+struct SharedCheck<'a> {
+    provider: &'a Provider,
+    props: &'a provider::Properties,
+}
+
+// SAFETY: `Provider` and `provider::Properties` contain raw pointers, so they are not `Sync`
+// on their own. `SharedCheck` is only constructed for providers declaring `thread_safe`,
+// whose `check` may be called from several threads at once with the same `data`.
+// The properties only point to strings owned by the caller, which are never written
+// and outlive the scoped threads sharing them.
+unsafe impl<'a> Sync for SharedCheck<'a> {}
+
 impl Provider {
     /// Checks a text for mistakes using given provider.
+    ///
+    /// Texts longer than the provider accepts are checked in chunks,
+    /// by as many threads as there are processors when the provider allows it.
+    #[allow(clippy::not_unsafe_ptr_arg_deref, clippy::ptr_arg)]
     pub fn check(&self, props: *const provider::Properties, text: &Cow<str>) -> Vec<Annotation> {
-        let text = CString::new(text.clone().into_owned()).expect("cannot create C string");
+        let props = unsafe { &*props };
+        let text: &str = text;
+        let Extensions {
+            max_text_length,
+            thread_safe,
+            ..
+        } = self.extensions;
+        if max_text_length == 0 || text.len() <= max_text_length {
+            return self.check_chunk(props, text);
+        }
 
-        let response = unsafe { (*self.internal).check(props, text.as_ptr()) };
-        let annotations = unsafe { &*response.annotations };
-        let length = annotations.len;
-        let mut anns = Vec::with_capacity(length);
+        let chunks = chunk::chunks(text, max_text_length);
+        let results: Vec<Vec<Annotation>> = if thread_safe {
+            let shared = SharedCheck {
+                provider: self,
+                props,
+            };
+            let shared = &shared;
+            let workers = std::thread::available_parallelism()
+                .map_or(1, |count| count.get())
+                .min(chunks.len());
+            // Workers take the chunks in order until there are none left.
+            let next = &AtomicUsize::new(0);
+            let chunks = &chunks;
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = (0..workers)
+                    .map(|_| {
+                        scope.spawn(move || {
+                            let mut checked = Vec::new();
+                            while let Some(chunk) = chunks.get(next.fetch_add(1, Ordering::Relaxed))
+                            {
+                                let annotations = shared
+                                    .provider
+                                    .check_chunk(shared.props, &text[chunk.clone()]);
+                                checked.push((chunk.start, annotations));
+                            }
+                            checked
+                        })
+                    })
+                    .collect();
+                let mut results: Vec<(usize, Vec<Annotation>)> = handles
+                    .into_iter()
+                    .flat_map(|handle| handle.join().expect("checking thread panicked"))
+                    .collect();
+                results.sort_by_key(|(start, _)| *start);
+                results
+                    .into_iter()
+                    .map(|(_, annotations)| annotations)
+                    .collect()
+            })
+        } else {
+            chunks
+                .iter()
+                .map(|chunk| self.check_chunk(props, &text[chunk.clone()]))
+                .collect()
+        };
+
+        chunks
+            .iter()
+            .zip(results)
+            .flat_map(|(chunk, annotations)| {
+                annotations.into_iter().map(move |mut ann| {
+                    ann.shift(chunk.start);
+                    ann
+                })
+            })
+            .collect()
+    }
+
+    /// Passes a text to the provider in a single call.
+    fn check_chunk(&self, props: &provider::Properties, text: &str) -> Vec<Annotation> {
+        let text = CString::new(text).expect("cannot create C string");
+
+        let provider_name = self.name().into_owned();
+        let mut anns = Vec::new();
 
         unsafe {
-            if !annotations.data.is_null() {
-                for i in 0..length {
-                    let provider::Annotation {
-                        offset,
-                        length,
-                        message,
-                        kind,
-                        suggestions,
-                    } = *annotations.data.offset(i as isize);
-
-                    let suggestions = &*suggestions;
-                    let suggestions = {
-                        let length = suggestions.len;
-                        let mut suggs = Vec::with_capacity(length);
-                        if !suggestions.data.is_null() {
-                            for i in 0..length {
-                                let sugg = *suggestions.data.offset(i as isize);
-                                suggs.push(CStr::from_ptr(sugg).to_string_lossy().into_owned())
-                            }
-                        }
-                        suggs
-                    };
+            let internal = self.internal;
+            let response = ((*internal).check)(props, text.as_ptr(), (*internal).data);
+            let annotations = &*response;
+            if !annotations.data.is_null() && annotations.len > 0 {
+                // Providers of version 1 end annotations right before `struct_size`.
+                let v1_size = mem::offset_of!(provider::Annotation, struct_size);
+                let stride = if self.version == 1 {
+                    v1_size
+                } else {
+                    ptr::read_unaligned(ptr::addr_of!((*annotations.data).struct_size))
+                };
+                if stride >= v1_size {
+                    anns.reserve(annotations.len);
+                    for i in 0..annotations.len {
+                        let ann = (annotations.data as *const u8).add(i * stride)
+                            as *const provider::Annotation;
+                        anns.push(read_annotation(ann, stride, self.version, &provider_name));
+                    }
+                }
+            }
+            ((*internal).free_annotations)(response);
+        }
+        anns
+    }
 
-                    anns.push(Annotation {
-                        offset,
-                        length,
-                        message: CStr::from_ptr(message).to_string_lossy().into_owned(),
-                        kind,
-                        suggestions,
+    /// Returns a string identifying the provider, its version and configuration.
+    pub fn identity(&self) -> String {
+        let mut identity = format!("{}\0{}", self.name(), self.library_identity);
+        unsafe {
+            if let Some(fingerprint) = self.extensions.fingerprint {
+                let fingerprint = fingerprint((*self.internal).data);
+                if !fingerprint.is_null() {
+                    identity.push('\0');
+                    identity.push_str(&CStr::from_ptr(fingerprint).to_string_lossy());
+                }
+            }
+        }
+        identity
+    }
+
+    /// Lists the rules the provider checks, empty when it does not support listing them.
+    pub fn rules(&self, props: &provider::Properties) -> Vec<Rule> {
+        let (list_rules, free_rules) =
+            match (self.extensions.list_rules, self.extensions.free_rules) {
+                (Some(list_rules), Some(free_rules)) => (list_rules, free_rules),
+                _ => return Vec::new(),
+            };
+        let provider_name = self.name().into_owned();
+        unsafe {
+            let rules = list_rules(props, (*self.internal).data);
+            if rules.is_null() {
+                return Vec::new();
+            }
+            let mut result = Vec::with_capacity((*rules).len);
+            let data = (*rules).data as *const u8;
+            let stride = if data.is_null() || (*rules).len == 0 {
+                0
+            } else {
+                ptr::read_unaligned(data as *const usize)
+            };
+            if stride >= mem::offset_of!(provider::RuleInfo, category) {
+                for i in 0..(*rules).len {
+                    let rule = data.add(i * stride) as *const provider::RuleInfo;
+                    let id = ptr::read_unaligned(ptr::addr_of!((*rule).id));
+                    let description = ptr::read_unaligned(ptr::addr_of!((*rule).description));
+                    result.push(Rule {
+                        id: CStr::from_ptr(id).to_string_lossy().into_owned(),
+                        description: CStr::from_ptr(description).to_string_lossy().into_owned(),
+                        category: field!(rule, stride, RuleInfo, category)
+                            .and_then(|s| optional_string(s)),
+                        enabled_by_default: field!(rule, stride, RuleInfo, enabled_by_default)
+                            .unwrap_or(true),
+                        provider: provider_name.clone(),
                     });
                 }
             }
+            free_rules(rules);
+            result
+        }
+    }
+
+    /// Whether the provider can be taught new words.
+    pub fn learns_words(&self) -> bool {
+        self.extensions.add_word.is_some()
+    }
+
+    pub fn add_word(&self, word: &str) {
+        self.word_call(self.extensions.add_word, word)
+    }
+
+    pub fn remove_word(&self, word: &str) {
+        self.word_call(self.extensions.remove_word, word)
+    }
+
+    pub fn ignore_word(&self, word: &str) {
+        self.word_call(self.extensions.ignore_word, word)
+    }
+
+    fn word_call(&self, function: Option<provider::WordFn>, word: &str) {
+        if let (Some(function), Ok(word)) = (function, CString::new(word)) {
+            unsafe { function(word.as_ptr(), (*self.internal).data) }
         }
-        anns
     }
 
     /// Get name of the provider provider.
-    pub fn name(&self) -> Cow<str> {
-        unsafe { CStr::from_ptr((*self.internal).name()).to_string_lossy() }
+    pub fn name(&self) -> Cow<'_, str> {
+        unsafe { CStr::from_ptr(((*self.internal).name)()).to_string_lossy() }
+    }
+}
+
+/// Copies an annotation of `size` bytes returned by the provider.
+unsafe fn read_annotation(
+    ann: *const provider::Annotation,
+    size: usize,
+    version: c_int,
+    provider_name: &str,
+) -> Annotation {
+    let offset = ptr::read_unaligned(ptr::addr_of!((*ann).offset));
+    let length = ptr::read_unaligned(ptr::addr_of!((*ann).length));
+    let message = ptr::read_unaligned(ptr::addr_of!((*ann).message));
+    let suggestions = ptr::read_unaligned(ptr::addr_of!((*ann).suggestions));
+    let provider = field!(ann, size, Annotation, provider)
+        .and_then(|provider| optional_string(provider))
+        .unwrap_or_else(|| provider_name.to_string());
+
+    Annotation {
+        offset,
+        length,
+        message: CStr::from_ptr(message).to_string_lossy().into_owned(),
+        kind: ptr::read_unaligned(ptr::addr_of!((*ann).kind)),
+        suggestions: if suggestions.is_null() {
+            Vec::new()
+        } else {
+            read_suggestions(&*suggestions, version)
+        },
+        contributors: vec![provider.clone()],
+        provider,
+        rule_id: field!(ann, size, Annotation, rule_id).and_then(|s| optional_string(s)),
+        category: field!(ann, size, Annotation, category).and_then(|s| optional_string(s)),
+        url: field!(ann, size, Annotation, url).and_then(|s| optional_string(s)),
+        severity: field!(ann, size, Annotation, severity).unwrap_or(Severity::Warning),
+        confidence: field!(ann, size, Annotation, confidence).and_then(optional_confidence),
+    }
+}
+
+/// Copies the suggestions of an annotation returned by the provider.
+unsafe fn read_suggestions(
+    suggestions: &provider::SuggestionArray,
+    version: c_int,
+) -> Vec<Suggestion> {
+    if suggestions.data.is_null() || suggestions.len == 0 {
+        return Vec::new();
+    }
+    let data = suggestions.data as *const u8;
+    // Providers of version 1 only return replacements.
+    if version == 1 {
+        return (0..suggestions.len)
+            .map(|i| {
+                let replacement = *(data as *const *const c_char).add(i);
+                Suggestion {
+                    replacement: CStr::from_ptr(replacement).to_string_lossy().into_owned(),
+                    description: None,
+                    confidence: None,
+                    range: None,
+                }
+            })
+            .collect();
+    }
+
+    let stride = ptr::read_unaligned(data as *const usize);
+    if stride < mem::offset_of!(provider::Suggestion, description) {
+        return Vec::new();
+    }
+    (0..suggestions.len)
+        .map(|i| {
+            let sugg = data.add(i * stride) as *const provider::Suggestion;
+            let replacement = ptr::read_unaligned(ptr::addr_of!((*sugg).replacement));
+            let has_range = field!(sugg, stride, Suggestion, has_range).unwrap_or(false);
+            let offset: Option<usize> = field!(sugg, stride, Suggestion, offset);
+            let length: Option<usize> = field!(sugg, stride, Suggestion, length);
+            Suggestion {
+                replacement: CStr::from_ptr(replacement).to_string_lossy().into_owned(),
+                description: field!(sugg, stride, Suggestion, description)
+                    .and_then(|s| optional_string(s)),
+                confidence: field!(sugg, stride, Suggestion, confidence)
+                    .and_then(optional_confidence),
+                range: match (offset, length) {
+                    (Some(offset), Some(length)) if has_range => Some(offset..offset + length),
+                    _ => None,
+                },
+            }
+        })
+        .collect()
+}
+
+/// Owner of the strings `provider::Properties` point to.
+struct CProperties {
+    primary_language: CString,
+    _rules: Vec<CString>,
+    disabled_rules: Vec<*const c_char>,
+    enabled_rules: Vec<*const c_char>,
+}
+
+impl CProperties {
+    fn new(primary_language: &str, disabled: &[String], enabled: &[String]) -> Self {
+        let to_c_string = |s: &String| CString::new(&**s).expect("cannot create C string");
+        let disabled: Vec<CString> = disabled.iter().map(to_c_string).collect();
+        let enabled: Vec<CString> = enabled.iter().map(to_c_string).collect();
+        Self {
+            primary_language: CString::new(primary_language)
+                .expect("Cannot create language C String"),
+            disabled_rules: disabled.iter().map(|rule| rule.as_ptr()).collect(),
+            enabled_rules: enabled.iter().map(|rule| rule.as_ptr()).collect(),
+            // Moving the strings does not move their contents.
+            _rules: disabled.into_iter().chain(enabled).collect(),
+        }
+    }
+
+    fn as_provider(&self) -> provider::Properties {
+        provider::Properties {
+            primary_language: self.primary_language.as_ptr(),
+            struct_size: mem::size_of::<provider::Properties>(),
+            disabled_rules: self.disabled_rules.as_ptr(),
+            disabled_rules_len: self.disabled_rules.len(),
+            enabled_rules: self.enabled_rules.as_ptr(),
+            enabled_rules_len: self.enabled_rules.len(),
+        }
+    }
+}
+
+/// Copies a nullable C string into an owned `String`.
+unsafe fn optional_string(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(ptr).to_string_lossy().into_owned())
+    }
+}
+
+unsafe fn read_field<T>(base: *const u8, size: usize, offset: usize) -> Option<T> {
+    if offset + mem::size_of::<T>() <= size {
+        Some(ptr::read_unaligned(base.add(offset) as *const T))
+    } else {
+        None
+    }
+}
+
+/// Providers use negative confidence when they do not know it,
+/// a value that is not a number is treated the same.
+fn optional_confidence(confidence: f32) -> Option<f32> {
+    if confidence < 0.0 || confidence.is_nan() {
+        None
+    } else {
+        Some(confidence)
     }
 }
 
@@ -99,44 +546,380 @@ impl Drop for Provider {
         unsafe {
             ((*self.internal).free_provider)(self.internal);
 
-            Box::from_raw(self.library);
+            // Providers created by tests are not loaded from a library.
+            if !self.library.is_null() {
+                drop(Box::from_raw(self.library));
+            }
         }
     }
 }
 
 /// Main struct holding providers and other relevant data.
+///
+/// After changing the public fields, call `configuration_changed`
+/// so that earlier results are not reused.
 pub struct Patronus {
     pub providers: Vec<Provider>,
+    /// Provider libraries that were found but refused,
+    /// and configuration that could not be read.
+    pub diagnostics: Vec<LoadDiagnostic>,
+    /// How to merge annotations of the same issue from different providers,
+    /// `None` (the default) reports all annotations as they are.
+    pub merge: Option<MergeOptions>,
+    /// Annotation kinds that are only reported when enough providers agree on them.
+    pub consensus: HashMap<AnnotationKind, Quorum>,
+    /// Cache of provider results, disabled when `None`.
+    pub cache: Option<Cache>,
+    /// Parts of texts that are hidden from providers, like URLs or code.
+    pub ignore: IgnorePatterns,
+    /// Words accepted as correctly spelled.
+    /// Use `add_word` and similar methods to change it so that providers learn the words too.
+    pub dictionary: Dictionary,
+    /// Rules turned on or off by the user.
+    pub rules: RuleSettings,
+    /// Whether `patronus-disable` and similar directives in comments of the text are obeyed.
+    pub suppressions: bool,
+    /// Changes with every change of the configuration.
+    generation: u64,
+}
+
+/// Source of `Patronus::generation` values, unique across instances.
+static GENERATIONS: AtomicU64 = AtomicU64::new(0);
+
+fn next_generation() -> u64 {
+    GENERATIONS.fetch_add(1, Ordering::Relaxed)
+}
+
+impl Default for Patronus {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Patronus {
-    /// Initializes Patronus and loads the providers.
+    /// Initializes Patronus and loads the providers
+    /// according to the policy from user configuration.
+    ///
+    /// When the configuration is invalid, no provider is loaded
+    /// and the problem is recorded in `diagnostics`. Likewise, an unreadable
+    /// personal dictionary is recorded there and an empty one is used instead.
     pub fn new() -> Self {
-        Self {
-            providers: Self::load_providers().expect("cannot load providers"),
+        let (policy, invalid_policy) = match LoadPolicy::from_config() {
+            Ok(policy) => (policy, None),
+            Err(err) => {
+                let diagnostic = LoadDiagnostic {
+                    path: policy::config_path().unwrap_or_default(),
+                    reason: Rejection::InvalidPolicy {
+                        message: err.to_string(),
+                    },
+                };
+                (LoadPolicy::deny_all(), Some(diagnostic))
+            }
+        };
+        let mut patronus = Self::with_policy(&policy).expect("cannot load providers");
+        patronus.diagnostics.extend(invalid_policy);
+        match Dictionary::open_xdg() {
+            Ok(dictionary) => patronus.set_dictionary(dictionary),
+            Err(err) => patronus.diagnostics.push(LoadDiagnostic {
+                path: dictionary::xdg_path().unwrap_or_default(),
+                reason: Rejection::InvalidDictionary {
+                    message: err.to_string(),
+                },
+            }),
         }
+        patronus
+    }
+
+    /// Initializes Patronus and loads the providers permitted by given policy.
+    pub fn with_policy(policy: &LoadPolicy) -> Result<Self, Error> {
+        let mut diagnostics = Vec::new();
+        let providers = Self::load_providers(policy, &mut diagnostics)?;
+        Ok(Self {
+            providers,
+            diagnostics,
+            merge: None,
+            consensus: HashMap::new(),
+            cache: None,
+            ignore: IgnorePatterns::default(),
+            dictionary: Dictionary::new(),
+            rules: RuleSettings::default(),
+            suppressions: true,
+            generation: next_generation(),
+        })
+    }
+
+    /// Identifies the configuration, so that results of earlier checks can be discarded
+    /// when it changes, like `Document` does.
+    ///
+    /// The methods changing the configuration update it. After changing
+    /// the public fields directly, call `configuration_changed`.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Records a change of the configuration made through the public fields,
+    /// e.g. of `providers` or `ignore`.
+    pub fn configuration_changed(&mut self) {
+        self.generation = next_generation();
+    }
+
+    /// Lists the rules providers check for given language.
+    pub fn rules(&self, language: &str) -> Vec<Rule> {
+        let props = CProperties::new(language, &[], &[]);
+        let props = props.as_provider();
+        self.providers
+            .iter()
+            .flat_map(|provider| provider.rules(&props))
+            .collect()
+    }
+
+    /// Turns on a rule for all languages, or for those covered by given language tag.
+    pub fn enable_rule(&mut self, id: &str, language: Option<&str>) {
+        self.rules.set(id, true, language);
+        self.configuration_changed();
+    }
+
+    /// Turns off a rule for all languages, or for those covered by given language tag.
+    pub fn disable_rule(&mut self, id: &str, language: Option<&str>) {
+        self.rules.set(id, false, language);
+        self.configuration_changed();
+    }
+
+    /// Replaces the dictionary, teaching providers its words
+    /// and making them forget the words of the previous one.
+    pub fn set_dictionary(&mut self, dictionary: Dictionary) {
+        let words: HashSet<&str> = dictionary.words().collect();
+        let session: HashSet<&str> = dictionary.session_words().collect();
+        for provider in &self.providers {
+            let previous = self
+                .dictionary
+                .words()
+                .chain(self.dictionary.session_words());
+            for word in previous {
+                if !words.contains(word) && !session.contains(word) {
+                    provider.remove_word(word);
+                }
+            }
+            for &word in &words {
+                provider.add_word(word);
+            }
+            for &word in &session {
+                provider.ignore_word(word);
+            }
+        }
+        self.dictionary = dictionary;
+        self.configuration_changed();
+    }
+
+    /// Adds a word to the personal dictionary and teaches it to providers.
+    pub fn add_word(&mut self, word: &str) -> Result<(), Error> {
+        if self.dictionary.add(word)? {
+            for provider in &self.providers {
+                provider.add_word(word);
+            }
+            self.configuration_changed();
+        }
+        Ok(())
+    }
+
+    /// Removes a word from the personal dictionary and makes providers forget it.
+    /// Words that are not in the dictionary are left alone,
+    /// so that providers do not reject words they know by themselves.
+    pub fn remove_word(&mut self, word: &str) -> Result<(), Error> {
+        if self.dictionary.remove(word)? {
+            for provider in &self.providers {
+                provider.remove_word(word);
+            }
+            self.configuration_changed();
+        }
+        Ok(())
+    }
+
+    /// Accepts a word until Patronus is dropped.
+    pub fn ignore_word_for_session(&mut self, word: &str) {
+        self.dictionary.ignore_for_session(word);
+        for provider in &self.providers {
+            provider.ignore_word(word);
+        }
+        self.configuration_changed();
+    }
+
+    /// Loads the project word lists from `.patronus` directories of given path
+    /// and its ancestors, replacing the ones loaded before. Returns the loaded files.
+    pub fn load_project_dictionary(&mut self, path: &Path) -> Result<Vec<PathBuf>, Error> {
+        let loaded = self.dictionary.load_project(path);
+        self.configuration_changed();
+        loaded
     }
 
     /// Checks a text for mistakes using all loaded providers.
+    /// Parts of the text matching `ignore` patterns are not checked.
+    #[allow(clippy::ptr_arg)]
     pub fn check(&self, props: &Properties, text: &Cow<str>) -> Vec<Annotation> {
-        let primary_language =
-            CString::new(&*props.primary_language).expect("Cannot create language C String");
-        let properties = provider::Properties {
-            primary_language: primary_language.as_ptr(),
+        self.check_str(props, text)
+    }
+
+    /// Checks a text like `check`, with given rule settings instead of `rules`.
+    pub fn check_with_rules(
+        &self,
+        props: &Properties,
+        text: &str,
+        rules: &RuleSettings,
+    ) -> Vec<Annotation> {
+        self.check_with(props, text, rules)
+    }
+
+    pub(crate) fn check_str(&self, props: &Properties, text: &str) -> Vec<Annotation> {
+        self.check_with(props, text, &self.rules)
+    }
+
+    fn check_with(&self, props: &Properties, text: &str, rules: &RuleSettings) -> Vec<Annotation> {
+        let suppressions = if self.suppressions {
+            Suppressions::parse(text)
+        } else {
+            Suppressions::default()
         };
+        let (text, masked) = self.ignore.mask(text);
+        let text = &*text;
+        let (disabled, enabled) = rules.partition(&props.primary_language);
+        let c_properties = CProperties::new(&props.primary_language, &disabled, &enabled);
+        let properties = c_properties.as_provider();
 
         let mut res = Vec::new();
         for provider in &self.providers {
-            res.extend(provider.check(&properties, text))
+            match self.cache {
+                Some(ref cache) => {
+                    let mut identity = provider.identity();
+                    // Results depend on the rules the provider was asked to check.
+                    for (prefix, rules) in [("-", &disabled), ("+", &enabled)] {
+                        for rule in rules {
+                            identity.push('\0');
+                            identity.push_str(prefix);
+                            identity.push_str(rule);
+                        }
+                    }
+                    if provider.learns_words() {
+                        // Results depend on the words the provider was taught.
+                        identity.push('\0');
+                        identity.push_str(&self.dictionary.fingerprint());
+                    }
+                    // Paragraphs are cached separately so that editing one of them
+                    // does not discard the results of the others.
+                    for paragraph in provider::segment::paragraphs(text) {
+                        let paragraph_text = &text[paragraph.clone()];
+                        let key = Cache::key(paragraph_text, props, &identity);
+                        let annotations = cache.get(&key).unwrap_or_else(|| {
+                            let annotations = provider.check(&properties, &paragraph_text.into());
+                            cache.insert(&key, &annotations);
+                            annotations
+                        });
+                        res.extend(annotations.into_iter().map(|mut ann| {
+                            ann.shift(paragraph.start);
+                            ann
+                        }));
+                    }
+                }
+                None => res.extend(provider.check(&properties, &text.into())),
+            }
+        }
+        let res = match self.merge {
+            Some(ref options) => merge(res, options),
+            None => res,
+        };
+        let res = filter_disabled(res, &disabled);
+        let res = filter_known(res, text, props, &self.dictionary);
+        let res = suppressions.apply(res, text);
+        filter_masked(apply_consensus(res, &self.consensus), &masked)
+    }
+
+    /// Checks a text derived from a document, e.g. by stripping markup,
+    /// and returns annotations with ranges in the original document.
+    pub fn check_mapped(&self, props: &Properties, map: &SourceMap) -> Vec<Annotation> {
+        self.check_str(props, map.text())
+            .into_iter()
+            .filter(|ann| !map.in_placeholder(&(ann.offset..ann.offset + ann.length)))
+            .map(|ann| map.annotation_to_original(ann))
+            .collect()
+    }
+
+    /// Checks fragments extracted from a source document by one of the `format` front-ends
+    /// and returns annotations with ranges in the source, ordered by their offset.
+    pub fn check_fragments(
+        &self,
+        props: &Properties,
+        source: &str,
+        fragments: &[Fragment],
+    ) -> Vec<Annotation> {
+        let res = self.check_extracted(props, fragments);
+        // Directives are in comments, which are not part of the extracted text.
+        if self.suppressions {
+            return Suppressions::parse(source).apply(res, source);
+        }
+        res
+    }
+
+    /// Checks the entries of a translation file one by one and returns their annotations
+    /// with positions in the file, in the order of the entries.
+    pub fn check_translations(
+        &self,
+        props: &Properties,
+        source: &str,
+        entries: &[translation::Entry],
+    ) -> Vec<translation::EntryAnnotation> {
+        // Directives are parsed once for the whole file rather than for every entry.
+        let suppressions = if self.suppressions {
+            Some(Suppressions::parse(source))
+        } else {
+            None
+        };
+        let mut res = Vec::new();
+        for entry in entries {
+            let mut annotations =
+                self.check_extracted(props, std::slice::from_ref(&entry.fragment));
+            if let Some(ref suppressions) = suppressions {
+                annotations = suppressions.apply(annotations, source);
+            }
+            res.extend(annotations.into_iter().map(|annotation| {
+                let (line, column) = translation::line_column(source, annotation.offset);
+                translation::EntryAnnotation {
+                    id: entry.id.clone(),
+                    line,
+                    column,
+                    annotation,
+                }
+            }));
         }
         res
     }
 
+    /// Checks fragments and returns annotations with ranges in the source,
+    /// ordered by their offset.
+    fn check_extracted(&self, props: &Properties, fragments: &[Fragment]) -> Vec<Annotation> {
+        let mut res: Vec<Annotation> = fragments
+            .iter()
+            .flat_map(|fragment| match fragment.language {
+                Some(ref language) => {
+                    let props = Properties {
+                        primary_language: language.clone(),
+                    };
+                    self.check_mapped(&props, &fragment.map)
+                }
+                None => self.check_mapped(props, &fragment.map),
+            })
+            .collect();
+        res.sort_by_key(|ann| ann.offset);
+        res
+    }
+
     /// Traverses provider directories and tries to load all shared libraries.
     /// The main provider directory is set during compile time from `PATRONUS_PROVIDER_DIR`
     /// environment variable (/usr/lib/patronus by default).
     /// Additionally, the directories listed in `PATRONUS_PROVIDER_PATH` at runtime are crawled as well.
-    fn load_providers() -> Result<Vec<Provider>, Error> {
+    /// Libraries rejected by the policy are not opened and are recorded in `diagnostics` instead.
+    fn load_providers(
+        policy: &LoadPolicy,
+        diagnostics: &mut Vec<LoadDiagnostic>,
+    ) -> Result<Vec<Provider>, Error> {
         let mut provider_locations = vec![PathBuf::from(env!("PATRONUS_PROVIDER_DIR"))];
         if let Some(provider_path) = env::var_os("PATRONUS_PROVIDER_PATH") {
             for path in env::split_paths(&provider_path) {
@@ -150,8 +933,15 @@ impl Patronus {
                 for entry in fs::read_dir(location).map_err(|source| Error::IoError { source })? {
                     let path = entry.map_err(|source| Error::IoError { source })?.path();
                     if path.is_file() && path.is_dylib() {
+                        let file = match policy.open(&path) {
+                            Ok(file) => file,
+                            Err(reason) => {
+                                diagnostics.push(LoadDiagnostic { path, reason });
+                                continue;
+                            }
+                        };
                         let lib = Box::new(unsafe {
-                            lib::Library::new(&path)
+                            lib::Library::new(policy::opened_path(&file, &path))
                                 .map_err(|source| Error::LibloadingError { source })?
                         });
                         let version = unsafe {
@@ -163,7 +953,7 @@ impl Patronus {
                             }
                         };
                         match version {
-                            1 => {
+                            1 | provider::PROVIDER_VERSION => {
                                 let internal_provider = unsafe {
                                     let init_provider: lib::Symbol<
                                         unsafe extern "C" fn() -> *mut provider::Provider,
@@ -175,12 +965,17 @@ impl Patronus {
                                 result.push(Provider {
                                     internal: internal_provider,
                                     library: Box::into_raw(lib),
+                                    version,
+                                    extensions: unsafe {
+                                        Extensions::read(internal_provider, version)
+                                    },
+                                    library_identity: library_identity(&path, &file),
                                 });
                             }
-                            _ => panic!(
-                                "Unsupported provider version {} for provider {:?}",
-                                version, path
-                            ),
+                            _ => diagnostics.push(LoadDiagnostic {
+                                path,
+                                reason: Rejection::UnsupportedVersion { version },
+                            }),
                         }
                     }
                 }
@@ -190,6 +985,18 @@ impl Patronus {
     }
 }
 
+/// Describes a library file so that its replacement can be detected.
+fn library_identity(path: &Path, file: &fs::File) -> String {
+    let modified = file
+        .metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|modified| modified.as_nanos())
+        .unwrap_or_default();
+    format!("{}\0{}", path.display(), modified)
+}
+
 trait DylibTestable {
     /// Checks whether given object is a dynamic library.
     fn is_dylib(&self) -> bool;
@@ -201,14 +1008,267 @@ trait DylibTestable {
 impl DylibTestable for Path {
     #[cfg(target_os = "macos")]
     fn is_dylib(&self) -> bool {
-        self.extension().map_or(false, |ext| ext == "dylib")
+        self.extension().is_some_and(|ext| ext == "dylib")
     }
     #[cfg(target_os = "windows")]
     fn is_dylib(&self) -> bool {
-        self.extension().map_or(false, |ext| ext == "dll")
+        self.extension().is_some_and(|ext| ext == "dll")
     }
     #[cfg(not(any(target_os = "windows", target_os = "macos")))]
     fn is_dylib(&self) -> bool {
-        self.extension().map_or(false, |ext| ext == "so")
+        self.extension().is_some_and(|ext| ext == "so")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::raw::c_void;
+    use std::sync::Mutex;
+
+    /// What a fake provider reports.
+    struct FakeData {
+        words: Vec<&'static str>,
+        kind: AnnotationKind,
+        rule_id: &'static [u8],
+        /// Calls of the word functions, like `+word`, `-word` or `~word`.
+        taught: Mutex<Vec<String>>,
+    }
+
+    unsafe extern "C" fn fake_check(
+        _props: *const provider::Properties,
+        text: *const c_char,
+        data: *mut c_void,
+    ) -> *mut provider::AnnotationArray {
+        let data = &*(data as *const FakeData);
+        let text = CStr::from_ptr(text).to_string_lossy();
+        let mut anns: Vec<provider::Annotation> = Vec::new();
+        for word in &data.words {
+            for (offset, _) in text.match_indices(word) {
+                let suggestions: provider::SuggestionArray = Vec::new().into();
+                anns.push(provider::Annotation {
+                    rule_id: data.rule_id.as_ptr() as *const c_char,
+                    severity: Severity::Error,
+                    ..provider::Annotation::new(
+                        offset,
+                        word.len(),
+                        b"Unknown word\0".as_ptr() as *const c_char,
+                        data.kind,
+                        Box::into_raw(Box::new(suggestions)),
+                    )
+                });
+            }
+        }
+        anns.sort_by_key(|ann| ann.offset);
+        Box::into_raw(Box::new(anns.into()))
+    }
+
+    unsafe extern "C" fn fake_free_annotations(ptr: *mut provider::AnnotationArray) {
+        let anns = Box::from_raw(ptr);
+        for i in 0..anns.len {
+            drop(Box::from_raw((*anns.data.add(i)).suggestions));
+        }
+    }
+
+    unsafe fn teach(word: *const c_char, data: *mut c_void, sign: char) {
+        let data = &*(data as *const FakeData);
+        let word = CStr::from_ptr(word).to_string_lossy();
+        data.taught
+            .lock()
+            .unwrap()
+            .push(format!("{}{}", sign, word));
+    }
+
+    unsafe extern "C" fn fake_add_word(word: *const c_char, data: *mut c_void) {
+        teach(word, data, '+')
+    }
+
+    unsafe extern "C" fn fake_remove_word(word: *const c_char, data: *mut c_void) {
+        teach(word, data, '-')
+    }
+
+    unsafe extern "C" fn fake_ignore_word(word: *const c_char, data: *mut c_void) {
+        teach(word, data, '~')
+    }
+
+    unsafe extern "C" fn fake_free_provider(ptr: *mut provider::Provider) {
+        let provider = Box::from_raw(ptr);
+        drop(Box::from_raw(provider.data as *mut FakeData));
+    }
+
+    extern "C" fn first_name() -> *const c_char {
+        b"First\0".as_ptr() as *const c_char
+    }
+
+    extern "C" fn second_name() -> *const c_char {
+        b"Second\0".as_ptr() as *const c_char
+    }
+
+    /// Creates an in-process provider, named “First” or “Second” after `index`,
+    /// reporting every occurrence of given words as an error of given kind.
+    pub fn provider(index: usize, words: &[&'static str], kind: AnnotationKind) -> Provider {
+        let data = Box::new(FakeData {
+            words: words.to_vec(),
+            kind,
+            rule_id: b"FAKE_RULE\0",
+            taught: Mutex::new(Vec::new()),
+        });
+        let name = [first_name, second_name][index];
+        let internal = provider::Provider {
+            thread_safe: true,
+            add_word: Some(fake_add_word),
+            remove_word: Some(fake_remove_word),
+            ignore_word: Some(fake_ignore_word),
+            ..provider::Provider::new(
+                name,
+                fake_check,
+                fake_free_annotations,
+                fake_free_provider,
+                Box::into_raw(data) as *mut c_void,
+            )
+        };
+        let internal = Box::into_raw(Box::new(internal));
+        Provider {
+            internal,
+            library: ptr::null_mut(),
+            version: provider::PROVIDER_VERSION,
+            extensions: unsafe { Extensions::read(internal, provider::PROVIDER_VERSION) },
+            library_identity: String::new(),
+        }
+    }
+
+    /// Takes the calls of the word functions of a provider created by `provider`.
+    pub fn taught(provider: &Provider) -> Vec<String> {
+        let data = unsafe { &*((*provider.internal).data as *const FakeData) };
+        std::mem::take(&mut *data.taught.lock().unwrap())
+    }
+
+    /// Creates Patronus with given providers and no others.
+    pub fn patronus(providers: Vec<Provider>) -> Patronus {
+        let mut patronus = Patronus::with_policy(&LoadPolicy::deny_all()).unwrap();
+        patronus.providers = providers;
+        patronus.configuration_changed();
+        patronus
+    }
+
+    /// Checks an English text, returning the offsets and providers of the annotations.
+    pub fn check(patronus: &Patronus, text: &str) -> Vec<(usize, String)> {
+        let props = Properties {
+            primary_language: "en".to_string(),
+        };
+        patronus
+            .check(&props, &text.into())
+            .into_iter()
+            .map(|ann| (ann.offset, ann.provider))
+            .collect()
+    }
+
+    /// Creates a spelling error annotation for testing.
+    pub fn annotation(provider: &str, offset: usize, length: usize) -> Annotation {
+        Annotation {
+            offset,
+            length,
+            message: format!("{} message", provider),
+            kind: AnnotationKind::Spelling,
+            suggestions: Vec::new(),
+            provider: provider.to_string(),
+            contributors: vec![provider.to_string()],
+            rule_id: None,
+            category: None,
+            url: None,
+            severity: Severity::Error,
+            confidence: None,
+        }
+    }
+
+    /// Annotation as returned by providers of version 1.
+    #[repr(C)]
+    struct AnnotationV1 {
+        offset: usize,
+        length: usize,
+        message: *const c_char,
+        kind: AnnotationKind,
+        suggestions: *mut provider::PArray<*const c_char>,
+    }
+
+    #[test]
+    fn test_check() {
+        let mut patronus = patronus(vec![
+            provider(0, &["Thsi", "teh"], AnnotationKind::Spelling),
+            provider(1, &["teh"], AnnotationKind::Spelling),
+        ]);
+        let text = "Thsi is teh text.";
+        assert_eq!(
+            check(&patronus, text),
+            vec![
+                (0, "First".to_string()),
+                (8, "First".to_string()),
+                (8, "Second".to_string())
+            ]
+        );
+
+        patronus.merge = Some(MergeOptions::default());
+        patronus.disable_rule("FAKE_RULE", Some("cs"));
+        patronus.ignore_word_for_session("Thsi");
+        let anns = patronus.check(
+            &Properties {
+                primary_language: "en".to_string(),
+            },
+            &text.into(),
+        );
+        assert_eq!(anns.len(), 1);
+        assert_eq!(anns[0].contributors, vec!["First", "Second"]);
+        assert_eq!(anns[0].rule_id.as_deref(), Some("FAKE_RULE"));
+
+        patronus.disable_rule("FAKE_RULE", None);
+        assert_eq!(check(&patronus, text), vec![]);
+        let props = Properties {
+            primary_language: "en".to_string(),
+        };
+        let anns = patronus.check_with_rules(&props, text, &RuleSettings::default());
+        assert_eq!(anns.len(), 1);
+    }
+
+    #[test]
+    fn test_chunks() {
+        let mut checker = provider(0, &["teh"], AnnotationKind::Spelling);
+        checker.extensions.max_text_length = 12;
+        let patronus = patronus(vec![checker]);
+        let offsets: Vec<usize> = check(&patronus, &"teh cat. ".repeat(20))
+            .into_iter()
+            .map(|(offset, _)| offset)
+            .collect();
+        assert_eq!(offsets, (0..20).map(|i| i * 9).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_read_version_1_annotation() {
+        let suggestions: provider::PArray<*const c_char> =
+            vec![b"Too\0".as_ptr() as *const c_char].into();
+        let mut suggestions = Box::new(suggestions);
+        let ann = AnnotationV1 {
+            offset: 0,
+            length: 3,
+            message: b"Possible typo\0".as_ptr() as *const c_char,
+            kind: AnnotationKind::Spelling,
+            suggestions: &mut *suggestions,
+        };
+        let size = mem::size_of::<AnnotationV1>();
+        assert_eq!(size, mem::offset_of!(provider::Annotation, struct_size));
+
+        let ann = unsafe {
+            read_annotation(
+                &ann as *const AnnotationV1 as *const provider::Annotation,
+                size,
+                1,
+                "Old",
+            )
+        };
+        assert_eq!(ann.message, "Possible typo");
+        assert_eq!(ann.provider, "Old");
+        assert_eq!(ann.severity, Severity::Warning);
+        assert_eq!(ann.rule_id, None);
+        assert_eq!(ann.suggestions.len(), 1);
+        assert_eq!(ann.suggestions[0].replacement, "Too");
     }
 }
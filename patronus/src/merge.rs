@@ -0,0 +1,183 @@
+use Annotation;
+
+/// Configuration of merging annotations of the same issue reported by several providers.
+#[derive(Clone, Debug, Default)]
+pub struct MergeOptions {
+    /// Provider names from the most preferred one.
+    /// The message and rule of a merged annotation are taken from the preferred provider,
+    /// unlisted providers come last in the order they were loaded.
+    pub priority: Vec<String>,
+    /// Merge annotations with overlapping ranges, not just identical ones.
+    pub merge_overlapping: bool,
+}
+
+impl MergeOptions {
+    fn rank(&self, provider: &str) -> usize {
+        self.priority
+            .iter()
+            .position(|preferred| preferred == provider)
+            .unwrap_or(self.priority.len())
+    }
+
+    fn matches(&self, a: &Annotation, b: &Annotation) -> bool {
+        // E.g. a misspelling and a grammar issue of the same word are different issues.
+        if a.kind != b.kind {
+            return false;
+        }
+        if a.offset == b.offset && a.length == b.length {
+            return true;
+        }
+        self.merge_overlapping && a.offset < b.offset + b.length && b.offset < a.offset + a.length
+    }
+}
+
+/// Merges annotations describing the same issue reported by different providers.
+/// Only annotations of the same kind are considered the same issue.
+///
+/// Suggestions of the merged annotations are concatenated in the order of provider preference,
+/// skipping duplicate replacements. Names of all providers that reported the issue
+/// are kept in `contributors`.
+pub fn merge(mut annotations: Vec<Annotation>, options: &MergeOptions) -> Vec<Annotation> {
+    // Stable sort keeps the original order among annotations of the same provider.
+    annotations.sort_by_key(|ann| options.rank(&ann.provider));
+
+    let mut merged: Vec<Annotation> = Vec::with_capacity(annotations.len());
+    for ann in annotations {
+        let target = merged.iter_mut().find(|existing| {
+            !existing.contributors.contains(&ann.provider) && options.matches(existing, &ann)
+        });
+
+        match target {
+            Some(existing) => absorb(existing, ann),
+            None => merged.push(ann),
+        }
+    }
+
+    merged.sort_by_key(|ann| (ann.offset, ann.length));
+    merged
+}
+
+/// Adds the suggestions and provenance of a less preferred annotation to an existing one.
+fn absorb(existing: &mut Annotation, other: Annotation) {
+    let same_range = existing.offset == other.offset && existing.length == other.length;
+    for mut suggestion in other.suggestions {
+        if !same_range && suggestion.range.is_none() {
+            // Keep pointing at the text the other provider annotated.
+            suggestion.range = Some(other.offset..other.offset + other.length);
+        }
+        match existing
+            .suggestions
+            .iter_mut()
+            .find(|known| known.replacement == suggestion.replacement)
+        {
+            Some(known) => {
+                if known.confidence < suggestion.confidence {
+                    known.confidence = suggestion.confidence;
+                }
+            }
+            None => existing.suggestions.push(suggestion),
+        }
+    }
+
+    for contributor in other.contributors {
+        if !existing.contributors.contains(&contributor) {
+            existing.contributors.push(contributor);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {tests, AnnotationKind, Suggestion};
+
+    fn annotation(
+        provider: &str,
+        offset: usize,
+        length: usize,
+        suggestions: &[&str],
+    ) -> Annotation {
+        let mut ann = tests::annotation(provider, offset, length);
+        ann.suggestions = suggestions
+            .iter()
+            .map(|replacement| Suggestion {
+                replacement: replacement.to_string(),
+                description: None,
+                confidence: None,
+                range: None,
+            })
+            .collect();
+        ann
+    }
+
+    fn replacements(ann: &Annotation) -> Vec<&str> {
+        ann.suggestions
+            .iter()
+            .map(|sugg| sugg.replacement.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_identical() {
+        let options = MergeOptions {
+            priority: vec!["LanguageTool".to_string()],
+            merge_overlapping: false,
+        };
+        let merged = merge(
+            vec![
+                annotation("Enchant", 0, 5, &["hello", "hallo"]),
+                annotation("Enchant", 10, 4, &[]),
+                annotation("LanguageTool", 0, 5, &["hallo", "hullo"]),
+            ],
+            &options,
+        );
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].message, "LanguageTool message");
+        assert_eq!(merged[0].contributors, vec!["LanguageTool", "Enchant"]);
+        assert_eq!(replacements(&merged[0]), vec!["hallo", "hullo", "hello"]);
+        assert_eq!(merged[1].provider, "Enchant");
+    }
+
+    #[test]
+    fn test_merge_overlapping() {
+        let anns = vec![
+            annotation("Enchant", 4, 3, &["the"]),
+            annotation("LanguageTool", 0, 7, &["a the"]),
+        ];
+
+        assert_eq!(merge(anns.clone(), &MergeOptions::default()).len(), 2);
+
+        let options = MergeOptions {
+            priority: vec![],
+            merge_overlapping: true,
+        };
+        let merged = merge(anns, &options);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].provider, "Enchant");
+        assert_eq!(merged[0].suggestions[1].range, Some(0..7));
+    }
+
+    #[test]
+    fn test_different_kinds_not_merged() {
+        let mut grammar = annotation("LanguageTool", 0, 5, &[]);
+        grammar.kind = AnnotationKind::Grammar;
+        let merged = merge(
+            vec![annotation("Enchant", 0, 5, &[]), grammar],
+            &MergeOptions::default(),
+        );
+        assert_eq!(merged.len(), 2);
+    }
+
+    #[test]
+    fn test_same_provider_not_merged() {
+        let merged = merge(
+            vec![
+                annotation("LanguageTool", 0, 5, &[]),
+                annotation("LanguageTool", 0, 5, &[]),
+            ],
+            &MergeOptions::default(),
+        );
+        assert_eq!(merged.len(), 2);
+    }
+}
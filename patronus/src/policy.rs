@@ -0,0 +1,457 @@
+use error::Error;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use toml::Value;
+
+const CONFIG_FILE: &str = "config.toml";
+const CONFIG_SECTION: &str = "security";
+
+/// Rules deciding which provider libraries are allowed to be loaded.
+///
+/// Provider libraries are identified by their file name without extension
+/// (e.g. `libpatronus_enchant`).
+#[derive(Clone, Debug)]
+pub struct LoadPolicy {
+    /// Refuse libraries that are, or whose directory is, writable by other users.
+    pub reject_insecure_permissions: bool,
+    /// When set, only the listed providers are loaded.
+    /// Each provider can be pinned to a hex-encoded SHA-256 digest of its library.
+    pub allowed_providers: Option<HashMap<String, Option<String>>>,
+}
+
+impl Default for LoadPolicy {
+    fn default() -> Self {
+        Self {
+            reject_insecure_permissions: true,
+            allowed_providers: None,
+        }
+    }
+}
+
+impl LoadPolicy {
+    /// Reads the policy from `[security]` section of `patronus/config.toml`
+    /// in XDG config directories, falling back to the default policy.
+    ///
+    /// ```toml
+    /// [security]
+    /// reject_insecure_permissions = true
+    ///
+    /// [security.allowed_providers]
+    /// libpatronus_enchant = true
+    /// libpatronus_languagetool = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+    /// ```
+    pub fn from_config() -> Result<Self, Error> {
+        match config_path() {
+            Some(path) => Self::from_config_file(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Policy refusing all providers.
+    pub fn deny_all() -> Self {
+        Self {
+            reject_insecure_permissions: true,
+            allowed_providers: Some(HashMap::new()),
+        }
+    }
+
+    /// Reads the policy from given configuration file.
+    pub fn from_config_file(path: &Path) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path).map_err(|source| Error::IoError { source })?;
+        Self::from_config_str(&contents)
+    }
+
+    fn from_config_str(contents: &str) -> Result<Self, Error> {
+        let config: toml::Table =
+            contents
+                .parse()
+                .map_err(|err: toml::de::Error| Error::ConfigError {
+                    message: err.to_string(),
+                })?;
+
+        let mut policy = Self::default();
+        let section = match config.get(CONFIG_SECTION) {
+            Some(Value::Table(section)) => section,
+            Some(_) => return Err(config_error("`security` must be a table")),
+            None => return Ok(policy),
+        };
+
+        match section.get("reject_insecure_permissions") {
+            Some(&Value::Boolean(reject)) => policy.reject_insecure_permissions = reject,
+            Some(_) => {
+                return Err(config_error(
+                    "`security.reject_insecure_permissions` must be a boolean",
+                ))
+            }
+            None => {}
+        }
+
+        match section.get("allowed_providers") {
+            Some(Value::Table(allowed)) => {
+                let mut allowed_providers = HashMap::new();
+                for (name, pin) in allowed {
+                    let pin = match *pin {
+                        Value::Boolean(true) => None,
+                        Value::String(ref digest) => Some(digest.to_lowercase()),
+                        _ => {
+                            return Err(config_error(&format!(
+                            "`security.allowed_providers.{}` must be `true` or a SHA-256 digest",
+                            name
+                        )))
+                        }
+                    };
+                    allowed_providers.insert(name.clone(), pin);
+                }
+                policy.allowed_providers = Some(allowed_providers);
+            }
+            Some(_) => return Err(config_error("`security.allowed_providers` must be a table")),
+            None => {}
+        }
+
+        Ok(policy)
+    }
+
+    /// Decides whether the library at given path can be loaded.
+    pub fn check(&self, path: &Path) -> Result<(), Rejection> {
+        self.open(path).map(drop)
+    }
+
+    /// Opens the library at given path when it can be loaded.
+    ///
+    /// The checks are done on the opened file so it should be loaded through
+    /// `opened_path` rather than by its path, which could be replaced in the meantime.
+    pub fn open(&self, path: &Path) -> Result<File, Rejection> {
+        let name = provider_name(path);
+
+        let pin = match self.allowed_providers {
+            Some(ref allowed) => match allowed.get(&name) {
+                Some(pin) => pin.as_ref(),
+                None => return Err(Rejection::NotAllowed { name }),
+            },
+            None => None,
+        };
+
+        let file = File::open(path).map_err(unreadable)?;
+
+        if self.reject_insecure_permissions {
+            check_permissions(path, &file)?;
+        }
+
+        if let Some(expected) = pin {
+            let mut contents = Vec::new();
+            (&file).read_to_end(&mut contents).map_err(unreadable)?;
+            let actual = sha256_hex(&contents);
+            if actual != *expected {
+                return Err(Rejection::DigestMismatch {
+                    expected: expected.clone(),
+                    actual,
+                });
+            }
+        }
+
+        Ok(file)
+    }
+}
+
+/// Path of the configuration file containing the policy, if there is one.
+pub fn config_path() -> Option<PathBuf> {
+    xdg::BaseDirectories::with_prefix("patronus")
+        .ok()
+        .and_then(|xdg_dirs| xdg_dirs.find_config_file(CONFIG_FILE))
+}
+
+/// Path through which the library opened by `LoadPolicy::open` can be loaded,
+/// so that the loaded file is the one that was checked.
+#[cfg(target_os = "linux")]
+pub fn opened_path(file: &File, path: &Path) -> PathBuf {
+    use std::os::unix::io::AsRawFd;
+
+    let fd_path = PathBuf::from(format!("/proc/self/fd/{}", file.as_raw_fd()));
+    // Without procfs, there is nothing better than the path.
+    if fd_path.exists() {
+        fd_path
+    } else {
+        path.to_path_buf()
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn opened_path(file: &File, path: &Path) -> PathBuf {
+    use std::os::unix::io::AsRawFd;
+
+    let fd_path = PathBuf::from(format!("/dev/fd/{}", file.as_raw_fd()));
+    if fd_path.exists() {
+        fd_path
+    } else {
+        path.to_path_buf()
+    }
+}
+
+#[cfg(not(unix))]
+pub fn opened_path(_file: &File, path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
+
+fn unreadable(err: std::io::Error) -> Rejection {
+    Rejection::Unreadable {
+        message: err.to_string(),
+    }
+}
+
+fn config_error(message: &str) -> Error {
+    Error::ConfigError {
+        message: message.to_string(),
+    }
+}
+
+/// Name under which the provider library is referred to in the policy.
+pub fn provider_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Ensures neither the library nor any directory leading to it can be modified by other users.
+///
+/// The path is checked both as listed and with symbolic links resolved,
+/// and the opened file must be the one the path leads to.
+#[cfg(unix)]
+fn check_permissions(path: &Path, file: &File) -> Result<(), Rejection> {
+    use std::os::unix::fs::MetadataExt;
+
+    let euid = unsafe { libc::geteuid() };
+    let opened = file.metadata().map_err(unreadable)?;
+    if !is_secure(&opened, euid, false) {
+        return Err(Rejection::InsecurePermissions {
+            path: path.to_path_buf(),
+        });
+    }
+
+    let listed = std::path::absolute(path).map_err(unreadable)?;
+    check_ancestors(&listed, euid)?;
+    let resolved = listed.canonicalize().map_err(unreadable)?;
+    if resolved != listed {
+        check_ancestors(&resolved, euid)?;
+    }
+
+    let metadata = fs::metadata(&resolved).map_err(unreadable)?;
+    if (metadata.dev(), metadata.ino()) != (opened.dev(), opened.ino()) {
+        return Err(Rejection::Unreadable {
+            message: "library was replaced while being checked".to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Checks the path and all its ancestors without following symbolic links.
+#[cfg(unix)]
+fn check_ancestors(path: &Path, euid: u32) -> Result<(), Rejection> {
+    for (depth, ancestor) in path.ancestors().enumerate() {
+        let metadata = fs::symlink_metadata(ancestor).map_err(unreadable)?;
+        // Other users cannot rename our entries of a sticky directory like `/tmp`
+        // but they could add libraries to the library’s own directory.
+        if !is_secure(&metadata, euid, depth > 1) {
+            return Err(Rejection::InsecurePermissions {
+                path: ancestor.to_path_buf(),
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_secure(metadata: &fs::Metadata, euid: u32, allow_sticky: bool) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    let owned = metadata.uid() == euid || metadata.uid() == 0;
+    // Permissions of symbolic links are meaningless, their targets are checked separately.
+    if metadata.file_type().is_symlink() {
+        return owned;
+    }
+    let writable = metadata.mode() & 0o022 != 0;
+    let sticky = metadata.mode() & 0o1000 != 0;
+    owned && (!writable || (allow_sticky && sticky))
+}
+
+#[cfg(not(unix))]
+fn check_permissions(_path: &Path, _file: &File) -> Result<(), Rejection> {
+    Ok(())
+}
+
+/// Reason for refusing to load a provider library.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+    /// The file or a directory leading to it is writable by other users.
+    InsecurePermissions { path: PathBuf },
+    /// The provider is missing from the allow-list.
+    NotAllowed { name: String },
+    /// The library does not match the pinned digest.
+    DigestMismatch { expected: String, actual: String },
+    /// The library could not be inspected.
+    Unreadable { message: String },
+    /// The library uses a provider interface version this version of Patronus does not support.
+    UnsupportedVersion { version: i32 },
+    /// The policy could not be read so no provider is trusted.
+    InvalidPolicy { message: String },
+    /// The personal dictionary could not be read so an empty one is used.
+    InvalidDictionary { message: String },
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Rejection::*;
+        match *self {
+            InsecurePermissions { ref path } => {
+                write!(f, "{:?} is writable by other users", path)
+            }
+            NotAllowed { ref name } => write!(f, "provider {} is not allowed", name),
+            DigestMismatch {
+                ref expected,
+                ref actual,
+            } => write!(
+                f,
+                "SHA-256 digest {} does not match the pinned {}",
+                actual, expected
+            ),
+            Unreadable { ref message } => write!(f, "cannot inspect library: {}", message),
+            UnsupportedVersion { version } => {
+                write!(f, "unsupported provider version {}", version)
+            }
+            InvalidPolicy { ref message } => write!(f, "invalid policy: {}", message),
+            InvalidDictionary { ref message } => {
+                write!(f, "invalid personal dictionary: {}", message)
+            }
+        }
+    }
+}
+
+/// Record of a provider library that was not loaded.
+#[derive(Debug, Clone)]
+pub struct LoadDiagnostic {
+    pub path: PathBuf,
+    pub reason: Rejection,
+}
+
+impl fmt::Display for LoadDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "rejected {:?}: {}", self.path, self.reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+
+    fn library(dir: &Path, name: &str, contents: &[u8]) -> PathBuf {
+        let path = dir.join(name);
+        File::create(&path).unwrap().write_all(contents).unwrap();
+        path
+    }
+
+    #[cfg(unix)]
+    fn set_mode(path: &Path, mode: u32) {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_rejects_world_writable() {
+        let dir = tempfile::tempdir().unwrap();
+        set_mode(dir.path(), 0o755);
+        let path = library(dir.path(), "libprovider.so", b"");
+        set_mode(&path, 0o644);
+        assert_eq!(LoadPolicy::default().check(&path), Ok(()));
+
+        set_mode(&path, 0o666);
+        assert!(matches!(
+            LoadPolicy::default().check(&path),
+            Err(Rejection::InsecurePermissions { .. })
+        ));
+
+        set_mode(&path, 0o644);
+        set_mode(dir.path(), 0o777);
+        assert!(matches!(
+            LoadPolicy::default().check(&path),
+            Err(Rejection::InsecurePermissions { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_rejects_symlink_into_writable_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        set_mode(dir.path(), 0o755);
+        let shared = dir.path().join("shared");
+        fs::create_dir(&shared).unwrap();
+        let target = library(&shared, "libprovider.so", b"");
+        set_mode(&target, 0o644);
+        let link = dir.path().join("libprovider.so");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        set_mode(&shared, 0o755);
+        assert_eq!(LoadPolicy::default().check(&link), Ok(()));
+
+        set_mode(&shared, 0o777);
+        assert!(matches!(
+            LoadPolicy::default().check(&link),
+            Err(Rejection::InsecurePermissions { path }) if path.ends_with("shared")
+        ));
+    }
+
+    #[test]
+    fn test_allow_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = library(dir.path(), "libprovider.so", b"test");
+        let mut allowed = HashMap::new();
+        allowed.insert(
+            "libprovider".to_string(),
+            Some("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08".to_string()),
+        );
+        let mut policy = LoadPolicy {
+            reject_insecure_permissions: false,
+            allowed_providers: Some(allowed),
+        };
+        assert_eq!(policy.check(&path), Ok(()));
+
+        library(dir.path(), "libprovider.so", b"tampered");
+        assert!(matches!(
+            policy.check(&path),
+            Err(Rejection::DigestMismatch { .. })
+        ));
+
+        policy.allowed_providers = Some(HashMap::new());
+        assert_eq!(
+            policy.check(&path),
+            Err(Rejection::NotAllowed {
+                name: "libprovider".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_config() {
+        let policy = LoadPolicy::from_config_str(
+            "[security]\nreject_insecure_permissions = false\n\n[security.allowed_providers]\nlibpatronus_enchant = true\nlibpatronus_sample = \"ABCD\"\n",
+        )
+        .unwrap();
+        assert!(!policy.reject_insecure_permissions);
+        let allowed = policy.allowed_providers.unwrap();
+        assert_eq!(allowed["libpatronus_enchant"], None);
+        assert_eq!(allowed["libpatronus_sample"], Some("abcd".to_string()));
+
+        assert!(LoadPolicy::from_config_str("[security]\nallowed_providers = 1\n").is_err());
+    }
+}
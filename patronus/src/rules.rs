@@ -0,0 +1,133 @@
+use dictionary::matches_language;
+use std::collections::{BTreeMap, HashMap};
+use Annotation;
+
+/// Rule checked by a provider.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    /// Identifier matching `Annotation::rule_id`.
+    pub id: String,
+    pub description: String,
+    pub category: Option<String>,
+    /// Whether the rule is checked unless disabled.
+    pub enabled_by_default: bool,
+    /// Name of the provider checking the rule.
+    pub provider: String,
+}
+
+/// Rules turned on or off by the user, globally or for specific languages.
+#[derive(Clone, Debug, Default)]
+pub struct RuleSettings {
+    global: BTreeMap<String, bool>,
+    /// Settings overriding the global ones, keyed by language tag.
+    languages: HashMap<String, BTreeMap<String, bool>>,
+}
+
+impl RuleSettings {
+    /// Turns a rule on or off for all languages, or for those covered by given language tag,
+    /// e.g. `en` covers `en-GB`.
+    pub fn set(&mut self, id: &str, enabled: bool, language: Option<&str>) {
+        let settings = match language {
+            Some(language) => self.languages.entry(language.to_string()).or_default(),
+            None => &mut self.global,
+        };
+        settings.insert(id.to_string(), enabled);
+    }
+
+    /// Returns a rule to its default state.
+    pub fn reset(&mut self, id: &str, language: Option<&str>) {
+        match language {
+            Some(language) => {
+                if let Some(settings) = self.languages.get_mut(language) {
+                    settings.remove(id);
+                }
+            }
+            None => {
+                self.global.remove(id);
+            }
+        }
+    }
+
+    /// Returns the settings applying to given language, with the most specific tag winning.
+    pub fn resolve(&self, language: &str) -> BTreeMap<String, bool> {
+        let mut tags: Vec<&String> = self
+            .languages
+            .keys()
+            .filter(|tag| matches_language(tag, language))
+            .collect();
+        tags.sort_by_key(|tag| tag.len());
+
+        let mut result = self.global.clone();
+        for tag in tags {
+            result.extend(
+                self.languages[tag]
+                    .iter()
+                    .map(|(id, &enabled)| (id.clone(), enabled)),
+            );
+        }
+        result
+    }
+
+    /// Returns IDs of the disabled and enabled rules for given language.
+    pub fn partition(&self, language: &str) -> (Vec<String>, Vec<String>) {
+        let (disabled, enabled): (Vec<_>, Vec<_>) = self
+            .resolve(language)
+            .into_iter()
+            .partition(|&(_, enabled)| !enabled);
+        (
+            disabled.into_iter().map(|(id, _)| id).collect(),
+            enabled.into_iter().map(|(id, _)| id).collect(),
+        )
+    }
+}
+
+/// Discards annotations produced by disabled rules.
+pub fn filter_disabled(annotations: Vec<Annotation>, disabled: &[String]) -> Vec<Annotation> {
+    if disabled.is_empty() {
+        return annotations;
+    }
+    annotations
+        .into_iter()
+        .filter(|ann| {
+            ann.rule_id
+                .as_ref()
+                .is_none_or(|rule_id| !disabled.contains(rule_id))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tests::annotation;
+
+    #[test]
+    fn test_resolve() {
+        let mut settings = RuleSettings::default();
+        settings.set("COMMA_PARENTHESIS_WHITESPACE", false, None);
+        settings.set("PASSIVE_VOICE", true, None);
+        settings.set("PASSIVE_VOICE", false, Some("en"));
+        settings.set("COMMA_PARENTHESIS_WHITESPACE", true, Some("en-GB"));
+
+        let (disabled, enabled) = settings.partition("cs");
+        assert_eq!(disabled, vec!["COMMA_PARENTHESIS_WHITESPACE"]);
+        assert_eq!(enabled, vec!["PASSIVE_VOICE"]);
+
+        let (disabled, enabled) = settings.partition("en-GB");
+        assert_eq!(disabled, vec!["PASSIVE_VOICE"]);
+        assert_eq!(enabled, vec!["COMMA_PARENTHESIS_WHITESPACE"]);
+
+        settings.reset("PASSIVE_VOICE", Some("en"));
+        assert_eq!(settings.partition("en-US").1, vec!["PASSIVE_VOICE"]);
+    }
+
+    #[test]
+    fn test_filter_disabled() {
+        let mut disabled = annotation("LanguageTool", 0, 3);
+        disabled.rule_id = Some("PASSIVE_VOICE".to_string());
+        let anns = vec![disabled, annotation("Enchant", 4, 3)];
+        let kept = filter_disabled(anns, &["PASSIVE_VOICE".to_string()]);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].provider, "Enchant");
+    }
+}
@@ -0,0 +1,285 @@
+use std::ops::Range;
+use Annotation;
+
+/// Part of the derived text and where it came from.
+#[derive(Clone, Debug, PartialEq)]
+struct Segment {
+    derived: Range<usize>,
+    original: Range<usize>,
+    /// Whether the text was copied unchanged so that positions inside map one to one.
+    verbatim: bool,
+}
+
+/// Text derived from an original document, e.g. by stripping markup,
+/// together with the information needed to map positions back to the original.
+///
+/// The text is built by appending pieces in the order of the original document.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SourceMap {
+    text: String,
+    segments: Vec<Segment>,
+    /// Ranges of the derived text standing in for content that is not prose.
+    placeholders: Vec<Range<usize>>,
+}
+
+/// Word standing in for content that is not prose, like inline code or math,
+/// so that the sentence around it stays grammatical.
+pub const PLACEHOLDER: &str = "X";
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a map of a text that was not transformed at all.
+    pub fn identity(text: &str) -> Self {
+        let mut map = Self::new();
+        map.push_verbatim(text, 0);
+        map
+    }
+
+    /// The derived text.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Appends text copied unchanged from the original at given offset.
+    pub fn push_verbatim(&mut self, text: &str, original_offset: usize) {
+        self.push(text, original_offset..original_offset + text.len(), true);
+    }
+
+    /// Appends text standing for a range of the original, e.g. a decoded entity.
+    pub fn push_replacement(&mut self, text: &str, original: Range<usize>) {
+        self.push(text, original, false);
+    }
+
+    /// Appends a placeholder standing for a range of the original that is not prose.
+    /// Annotations lying within placeholders are not reported.
+    pub fn push_placeholder(&mut self, original: Range<usize>) {
+        let start = self.text.len();
+        self.push(PLACEHOLDER, original, false);
+        self.placeholders.push(start..self.text.len());
+    }
+
+    /// Whether a range of the derived text lies within a placeholder.
+    pub fn in_placeholder(&self, range: &Range<usize>) -> bool {
+        self.placeholders
+            .iter()
+            .any(|placeholder| placeholder.start <= range.start && range.end <= placeholder.end)
+    }
+
+    /// Appends text with no counterpart in the original, e.g. a separator of extracted parts.
+    pub fn push_inserted(&mut self, text: &str, original_offset: usize) {
+        self.push(text, original_offset..original_offset, false);
+    }
+
+    fn push(&mut self, text: &str, original: Range<usize>, verbatim: bool) {
+        if text.is_empty() {
+            return;
+        }
+        let derived = self.text.len()..self.text.len() + text.len();
+        self.text.push_str(text);
+        // Join with the previous verbatim segment when it continues it.
+        if let Some(last) = self.segments.last_mut() {
+            if verbatim && last.verbatim && last.original.end == original.start {
+                last.derived.end = derived.end;
+                last.original.end = original.end;
+                return;
+            }
+        }
+        self.segments.push(Segment {
+            derived,
+            original,
+            verbatim,
+        });
+    }
+
+    /// Maps a position in the derived text to the original.
+    /// Positions inside replaced text map to the start of the replaced range.
+    pub fn start_to_original(&self, position: usize) -> usize {
+        let index = self
+            .segments
+            .partition_point(|segment| segment.derived.end <= position);
+        match self.segments.get(index) {
+            Some(segment) if segment.verbatim => {
+                segment.original.start + position.saturating_sub(segment.derived.start)
+            }
+            Some(segment) => segment.original.start,
+            None => self.end_to_original(position),
+        }
+    }
+
+    /// Maps a position in the derived text to the original.
+    /// Positions inside replaced text map to the end of the replaced range.
+    pub fn end_to_original(&self, position: usize) -> usize {
+        let index = self
+            .segments
+            .partition_point(|segment| segment.derived.end < position);
+        match self.segments.get(index).or_else(|| self.segments.last()) {
+            Some(segment) if segment.verbatim => {
+                let inside = position.clamp(segment.derived.start, segment.derived.end);
+                segment.original.start + (inside - segment.derived.start)
+            }
+            Some(segment) if position <= segment.derived.start => segment.original.start,
+            Some(segment) => segment.original.end,
+            None => 0,
+        }
+    }
+
+    /// Maps a range of the derived text to the smallest range of the original covering it,
+    /// including any markup removed from the middle.
+    pub fn to_original(&self, range: Range<usize>) -> Range<usize> {
+        if range.is_empty() {
+            let start = self.start_to_original(range.start);
+            return start..start;
+        }
+        let start = self.start_to_original(range.start);
+        start..self.end_to_original(range.end).max(start)
+    }
+
+    /// Chains this map with the map `base` its original was derived from,
+    /// e.g. when Markdown extracted from doc comments is stripped of markup.
+    /// The resulting map leads from this text to the original of `base`.
+    pub fn compose(&self, base: &SourceMap) -> SourceMap {
+        let mut map = SourceMap {
+            placeholders: self.placeholders.clone(),
+            ..SourceMap::new()
+        };
+        for segment in &self.segments {
+            let text = &self.text[segment.derived.clone()];
+            if !segment.verbatim {
+                map.push(text, base.to_original(segment.original.clone()), false);
+                continue;
+            }
+            // Split the copied text where the base map changes segments.
+            let mut position = segment.original.start;
+            while position < segment.original.end {
+                let index = base
+                    .segments
+                    .partition_point(|base_segment| base_segment.derived.end <= position);
+                let end = base
+                    .segments
+                    .get(index)
+                    .map_or(segment.original.end, |base_segment| {
+                        base_segment.derived.end.min(segment.original.end)
+                    });
+                let piece = &text[position - segment.original.start..end - segment.original.start];
+                let verbatim = base.is_verbatim(&(position..end));
+                map.push(piece, base.to_original(position..end), verbatim);
+                position = end;
+            }
+        }
+        map
+    }
+
+    /// Whether a range of the derived text was copied from the original as a whole.
+    fn is_verbatim(&self, range: &Range<usize>) -> bool {
+        let index = self
+            .segments
+            .partition_point(|segment| segment.derived.end <= range.start);
+        self.segments.get(index).is_some_and(|segment| {
+            segment.verbatim
+                && segment.derived.start <= range.start
+                && range.end <= segment.derived.end
+        })
+    }
+
+    /// Translates an annotation of the derived text to the original.
+    ///
+    /// Suggestions are dropped when the annotated text does not appear in the original as is,
+    /// since applying them would destroy the markup in between.
+    pub fn annotation_to_original(&self, mut ann: Annotation) -> Annotation {
+        let range = ann.offset..ann.offset + ann.length;
+        let verbatim = self.is_verbatim(&range);
+        let mut suggestions = Vec::with_capacity(ann.suggestions.len());
+        for mut sugg in std::mem::take(&mut ann.suggestions) {
+            let sugg_range = sugg.range.clone().unwrap_or(range.clone());
+            if !self.is_verbatim(&sugg_range) {
+                continue;
+            }
+            if sugg.range.is_some() {
+                sugg.range = Some(self.to_original(sugg_range));
+            }
+            suggestions.push(sugg);
+        }
+        let original = self.to_original(range);
+        ann.offset = original.start;
+        ann.length = original.len();
+        ann.suggestions = if verbatim { suggestions } else { Vec::new() };
+        ann
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tests::annotation;
+    use Suggestion;
+
+    /// Builds the derived text of `Tou **manny** misteaks &amp; errors`.
+    fn markdown() -> SourceMap {
+        let mut map = SourceMap::new();
+        map.push_verbatim("Tou ", 0);
+        map.push_verbatim("manny", 6);
+        map.push_verbatim(" misteaks ", 13);
+        map.push_replacement("&", 23..28);
+        map.push_verbatim(" errors", 28);
+        map.push_inserted("\n\n", 35);
+        map
+    }
+
+    #[test]
+    fn test_positions() {
+        let map = markdown();
+        assert_eq!(map.text(), "Tou manny misteaks & errors\n\n");
+        assert_eq!(map.to_original(0..3), 0..3);
+        assert_eq!(map.to_original(4..9), 6..11);
+        // Straddling the removed `**`.
+        assert_eq!(map.to_original(0..9), 0..11);
+        assert_eq!(map.to_original(4..18), 6..22);
+        assert_eq!(map.to_original(19..20), 23..28);
+        assert_eq!(map.to_original(10..20), 14..28);
+        assert_eq!(map.to_original(27..29), 35..35);
+    }
+
+    #[test]
+    fn test_compose() {
+        // `/// Tou **manny**\n/// misteaks` with comment markers removed first.
+        let mut comment = SourceMap::new();
+        comment.push_verbatim("Tou **manny**", 4);
+        comment.push_inserted("\n", 17);
+        comment.push_verbatim("misteaks", 22);
+        let mut markdown = SourceMap::new();
+        markdown.push_verbatim("Tou ", 0);
+        markdown.push_verbatim("manny", 6);
+        markdown.push_verbatim("\nmisteaks", 13);
+
+        let map = markdown.compose(&comment);
+        assert_eq!(map.text(), "Tou manny\nmisteaks");
+        assert_eq!(map.to_original(4..9), 10..15);
+        assert_eq!(map.to_original(10..18), 22..30);
+        assert_eq!(map.to_original(4..18), 10..30);
+        assert!(!map.is_verbatim(&(4..18)));
+    }
+
+    #[test]
+    fn test_annotation() {
+        let map = markdown();
+        let mut ann = annotation("Enchant", 4, 5);
+        ann.suggestions.push(Suggestion {
+            replacement: "many".to_string(),
+            description: None,
+            confidence: None,
+            range: None,
+        });
+        let mapped = map.annotation_to_original(ann.clone());
+        assert_eq!((mapped.offset, mapped.length), (6, 5));
+        assert_eq!(mapped.suggestions.len(), 1);
+
+        ann.offset = 0;
+        ann.length = 9;
+        let mapped = map.annotation_to_original(ann);
+        assert_eq!((mapped.offset, mapped.length), (0, 11));
+        assert!(mapped.suggestions.is_empty());
+    }
+}
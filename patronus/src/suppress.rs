@@ -0,0 +1,269 @@
+use regex::Regex;
+use std::ops::Range;
+use std::sync::OnceLock;
+use {Annotation, AnnotationKind};
+
+/// Part of a text where some rules are not reported.
+#[derive(Clone, Debug, PartialEq)]
+struct Region {
+    range: Range<usize>,
+    /// Suppressed rule IDs, empty for all rules.
+    rules: Vec<String>,
+    /// Rule IDs enabled again when all rules are suppressed.
+    except: Vec<String>,
+}
+
+impl Region {
+    fn suppresses(&self, ann: &Annotation) -> bool {
+        let rule_id = ann.rule_id.as_ref();
+        self.range.contains(&ann.offset)
+            && if self.rules.is_empty() {
+                !rule_id.is_some_and(|rule_id| self.except.contains(rule_id))
+            } else {
+                rule_id.is_some_and(|rule_id| self.rules.contains(rule_id))
+            }
+    }
+}
+
+/// Directives in comments of a text silencing annotations in place.
+///
+/// The following directives are recognized in `<!-- … -->` and `/* … */` comments
+/// and after `//`, `#`, `%` or `;` at the start of a line or following a space,
+/// optionally followed by space or comma separated rule IDs (all rules when omitted).
+/// The comment has to end the line.
+///
+/// * `patronus-disable-next-line` suppresses annotations starting on the next line,
+/// * `patronus-disable` suppresses annotations until `patronus-enable` or the end of the text,
+///   `patronus-enable` with rule IDs only enables the listed rules again,
+/// * `patronus-ignore-word` accepts the listed words as correctly spelled in the whole text.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Suppressions {
+    regions: Vec<Region>,
+    words: Vec<String>,
+    /// Comments containing the directives, which should not be reported either.
+    directives: Vec<Range<usize>>,
+}
+
+fn directive_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(
+            r"(?m)(<!--|/\*|(?:^|[ \t])(?://|#|%|;))[ \t]*patronus-(disable-next-line|disable|enable|ignore-word)\b([^\n]*?)[ \t]*(-->|\*/)?[ \t]*$",
+        )
+        .expect("invalid directive pattern")
+    })
+}
+
+impl Suppressions {
+    /// Finds the directives in a text.
+    pub fn parse(text: &str) -> Self {
+        let mut result = Self::default();
+        // Blocks opened by `patronus-disable`, ending at the end of the text until closed.
+        let mut open: Vec<Region> = Vec::new();
+        for captures in directive_regex().captures_iter(text) {
+            // Block comments have to be closed, line comments must not be.
+            let closing = captures.get(4).map(|closing| closing.as_str());
+            let expected = match captures[1].trim_start() {
+                "<!--" => Some("-->"),
+                "/*" => Some("*/"),
+                _ => None,
+            };
+            if closing != expected {
+                continue;
+            }
+            let whole = captures.get(0).expect("match has a whole-match group");
+            let arguments: Vec<String> = captures[3]
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|argument| !argument.is_empty())
+                .map(str::to_string)
+                .collect();
+            result.directives.push(whole.range());
+            match &captures[2] {
+                "disable-next-line" => {
+                    let start = text[whole.end()..]
+                        .find('\n')
+                        .map_or(text.len(), |newline| whole.end() + newline + 1);
+                    let end = text[start..]
+                        .find('\n')
+                        .map_or(text.len(), |newline| start + newline);
+                    result.regions.push(Region {
+                        range: start..end,
+                        rules: arguments,
+                        except: Vec::new(),
+                    });
+                }
+                "disable" => open.push(Region {
+                    range: whole.end()..text.len(),
+                    rules: arguments,
+                    except: Vec::new(),
+                }),
+                "enable" => {
+                    // Blocks are closed here and the rules that were not enabled
+                    // stay suppressed in a block continuing after the directive.
+                    for mut region in std::mem::take(&mut open) {
+                        let mut rest = Region {
+                            range: whole.end()..text.len(),
+                            rules: region.rules.clone(),
+                            except: region.except.clone(),
+                        };
+                        region.range.end = whole.start();
+                        result.regions.push(region);
+                        if arguments.is_empty() {
+                            continue;
+                        }
+                        if rest.rules.is_empty() {
+                            rest.except.extend(arguments.iter().cloned());
+                            open.push(rest);
+                        } else {
+                            rest.rules.retain(|rule| !arguments.contains(rule));
+                            if !rest.rules.is_empty() {
+                                open.push(rest);
+                            }
+                        }
+                    }
+                }
+                _ => result.words.extend(arguments),
+            }
+        }
+        result.regions.extend(open);
+        result
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.directives.is_empty()
+    }
+
+    /// Discards annotations silenced by the directives or lying in the directives themselves.
+    pub fn apply(&self, annotations: Vec<Annotation>, text: &str) -> Vec<Annotation> {
+        if self.is_empty() {
+            return annotations;
+        }
+        annotations
+            .into_iter()
+            .filter(|ann| {
+                let ignored_word = ann.kind == AnnotationKind::Spelling
+                    && text
+                        .get(ann.offset..ann.offset + ann.length)
+                        .is_some_and(|word| self.words.iter().any(|known| known == word));
+                !ignored_word
+                    && !self
+                        .directives
+                        .iter()
+                        .any(|range| range.contains(&ann.offset))
+                    && !self.regions.iter().any(|region| region.suppresses(ann))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tests::{self, annotation};
+    use AnnotationKind;
+
+    fn kept(text: &str, anns: Vec<Annotation>) -> Vec<usize> {
+        Suppressions::parse(text)
+            .apply(anns, text)
+            .iter()
+            .map(|ann| ann.offset)
+            .collect()
+    }
+
+    fn ruled(offset: usize, rule_id: &str) -> Annotation {
+        let mut ann = annotation("LanguageTool", offset, 1);
+        ann.rule_id = Some(rule_id.to_string());
+        ann
+    }
+
+    #[test]
+    fn test_next_line() {
+        let text = "a\n<!-- patronus-disable-next-line PASSIVE_VOICE -->\nb c\nd";
+        let b = text.find("\nb").unwrap() + 1;
+        let d = text.find("\nd").unwrap() + 1;
+        let anns = vec![
+            ruled(0, "PASSIVE_VOICE"),
+            ruled(8, "MORFOLOGIK"),
+            ruled(b, "PASSIVE_VOICE"),
+            ruled(b + 2, "UPPERCASE"),
+            ruled(d, "PASSIVE_VOICE"),
+        ];
+        assert_eq!(kept(text, anns), vec![0, b + 2, d]);
+    }
+
+    #[test]
+    fn test_blocks() {
+        let text = "a\n% patronus-disable\nb\n% patronus-disable A, B\nc\n% patronus-enable\nd\n# patronus-disable C\ne";
+        let offset = |c: char| text.find(&format!("\n{}", c)).map_or(0, |line| line + 1);
+        let anns = vec![
+            ruled(offset('a'), "A"),
+            ruled(offset('b'), "A"),
+            ruled(offset('c'), "B"),
+            ruled(offset('d'), "A"),
+            ruled(offset('e'), "A"),
+            ruled(offset('e'), "C"),
+        ];
+        assert_eq!(
+            kept(text, anns),
+            vec![offset('a'), offset('d'), offset('e')]
+        );
+    }
+
+    #[test]
+    fn test_enable_some() {
+        let text = "<!-- patronus-disable -->\na\n<!-- patronus-enable A -->\nb\n<!-- patronus-disable B C -->\nc\n<!-- patronus-enable C -->\nd";
+        let offset = |c: char| text.find(&format!("\n{}", c)).unwrap() + 1;
+        let anns = vec![
+            ruled(offset('a'), "A"),
+            ruled(offset('b'), "A"),
+            ruled(offset('b'), "B"),
+            ruled(offset('c'), "A"),
+            ruled(offset('d'), "A"),
+            ruled(offset('d'), "C"),
+        ];
+        assert_eq!(
+            kept(text, anns),
+            vec![offset('b'), offset('c'), offset('d'), offset('d')]
+        );
+    }
+
+    #[test]
+    fn test_comment_syntax() {
+        for text in [
+            "Costs 5% patronus-disable\na",
+            "Issue#patronus-disable\na",
+            "<!-- patronus-disable\na",
+            "# patronus-disable -->\na",
+            "<!-- patronus-disable --> text\na",
+        ] {
+            assert!(Suppressions::parse(text).is_empty(), "{}", text);
+        }
+        for text in [
+            "  # patronus-disable\na",
+            "x = 1; // patronus-disable\na",
+            "Text <!-- patronus-disable -->\na",
+            "/* patronus-disable */\na",
+        ] {
+            assert!(
+                kept(text, vec![ruled(text.len() - 1, "A")]).is_empty(),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_ignore_word() {
+        let patronus = tests::patronus(vec![tests::provider(
+            0,
+            &["rustc", "rustup"],
+            AnnotationKind::Spelling,
+        )]);
+        // The directive itself is not reported either.
+        let text = "// patronus-ignore-word rustc cargo\nrustc and rustup";
+        assert_eq!(
+            tests::check(&patronus, text),
+            vec![(46, "First".to_string())]
+        );
+    }
+}
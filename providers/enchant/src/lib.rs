@@ -1,14 +1,102 @@
 extern crate enchant;
 extern crate patronus_provider;
 
-use enchant::Broker;
+use enchant::{Broker, Dict};
 use patronus_provider::*;
+use std::collections::HashMap;
+use std::env;
 use std::ffi::{CStr, CString};
+use std::fs;
 use std::os::raw::{c_char, c_int, c_void};
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+/// Change of the words known to the dictionaries.
+enum WordChange {
+    /// Word added to Enchant’s personal word list.
+    Add(String),
+    /// Word removed from Enchant’s personal word list.
+    Remove(String),
+    /// Word accepted until the provider is freed.
+    Ignore(String),
+}
+
+/// Data kept between calls.
+struct State {
+    /// Opened dictionaries with the number of `changes` applied to them.
+    /// Declared before the broker so that they are freed first.
+    dicts: HashMap<String, (Dict, usize)>,
+    broker: Broker,
+    /// Words Patronus taught us, applied to each dictionary when it is used.
+    changes: Vec<WordChange>,
+    /// Value returned by `fingerprint`, updated whenever the words change.
+    fingerprint: CString,
+}
+
+impl State {
+    fn dict(&mut self, lang: &str) -> Option<&mut Dict> {
+        if !self.dicts.contains_key(lang) {
+            let dict = self.broker.request_dict(lang).ok()?;
+            self.dicts.insert(lang.to_string(), (dict, 0));
+        }
+        let (dict, applied) = self.dicts.get_mut(lang).expect("dictionary was just opened");
+        for change in &self.changes[*applied..] {
+            match *change {
+                WordChange::Add(ref word) => dict.add(word),
+                // Note that Enchant also rejects the word from then on,
+                // even when the system dictionary contains it.
+                WordChange::Remove(ref word) => dict.remove(word),
+                WordChange::Ignore(ref word) => dict.add_to_session(word),
+            }
+        }
+        *applied = self.changes.len();
+        Some(dict)
+    }
+
+    fn change_words(&mut self, change: WordChange) {
+        self.changes.push(change);
+        // Apply the change to the opened dictionaries right away
+        // so that the fingerprint describes the updated word lists.
+        let langs: Vec<String> = self.dicts.keys().cloned().collect();
+        for lang in langs {
+            self.dict(&lang);
+        }
+        self.update_fingerprint();
+    }
+
+    /// Describes the personal word lists so that cached results are discarded
+    /// when they are changed, possibly by another application before we started.
+    fn update_fingerprint(&mut self) {
+        let mut files: Vec<String> = personal_directory()
+            .and_then(|directory| fs::read_dir(directory).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let metadata = entry.metadata().ok()?;
+                let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+                Some(format!(
+                    "{} {} {}",
+                    entry.file_name().to_string_lossy(),
+                    metadata.len(),
+                    modified.as_nanos()
+                ))
+            })
+            .collect();
+        files.sort();
+        self.fingerprint = CString::new(format!(
+            "{} {} {}",
+            env!("CARGO_PKG_VERSION"),
+            self.changes.len(),
+            files.join(" ")
+        ))
+        .expect("cannot create C string");
+    }
+}
 
 #[no_mangle]
 pub extern "C" fn patronus_provider_version() -> c_int {
-    1
+    PROVIDER_VERSION
 }
 
 #[no_mangle]
@@ -21,40 +109,48 @@ extern "C" fn check_text(
     text: *const c_char,
     data: *mut c_void,
 ) -> *mut AnnotationArray {
-    let broker = unsafe { &mut *(data as *mut Broker) };
+    let state = unsafe { &mut *(data as *mut State) };
 
     let lang = unsafe { CStr::from_ptr((*props).primary_language).to_string_lossy() };
     let text = unsafe { CStr::from_ptr(text).to_string_lossy().into_owned() };
 
-    let mut offset = 0;
     let mut result = Vec::new();
 
-    if let Ok(dict) = broker.request_dict(&lang) {
-        let words = text.split(|c: char| !c.is_alphabetic());
-        for word in words {
-            let length = word.chars().count();
-            if length > 0 {
-                if !dict.check(word).unwrap_or(true) {
-                    let suggestions: Vec<Suggestion> = dict
-                        .suggest(word)
-                        .into_iter()
-                        .map(|sugg| {
+    if let Some(dict) = state.dict(&lang) {
+        for range in segment::words(&text) {
+            let word = &text[range.clone()];
+            // Numbers are not a matter of spelling.
+            if !word.chars().any(char::is_alphabetic) {
+                continue;
+            }
+            if !dict.check(word).unwrap_or(true) {
+                let suggestions: Vec<Suggestion> = dict
+                    .suggest(word)
+                    .into_iter()
+                    .map(|sugg| {
+                        Suggestion::new(
                             CString::new(sugg)
                                 .expect("cannot create C string")
-                                .into_raw() as *const c_char
-                        })
-                        .collect();
-                    let ann = Annotation {
-                        offset: offset,
-                        length: length,
-                        message: static_cstr!("Word was not found in the dictionary"),
-                        kind: AnnotationKind::Spelling,
-                        suggestions: Box::into_raw(Box::new(suggestions.into())),
-                    };
-                    result.push(ann);
-                }
+                                .into_raw(),
+                        )
+                    })
+                    .collect();
+                // Enchant orders suggestions by likelihood but does not say how sure it is,
+                // so the confidence stays unknown.
+                let ann = Annotation {
+                    rule_id: static_cstr!("ENCHANT_SPELLING"),
+                    category: static_cstr!("Spelling"),
+                    severity: Severity::Error,
+                    ..Annotation::new(
+                        range.start,
+                        range.len(),
+                        static_cstr!("Word was not found in the dictionary"),
+                        AnnotationKind::Spelling,
+                        Box::into_raw(Box::new(suggestions.into())),
+                    )
+                };
+                result.push(ann);
             }
-            offset += length + 1;
         }
     }
     Box::into_raw(Box::new(result.into()))
@@ -66,8 +162,8 @@ unsafe extern "C" fn free_annotations(ptr: *mut AnnotationArray) {
         let ann = &*anns.data.offset(i as isize);
         let suggs = Box::from_raw(ann.suggestions);
         for i in 0..suggs.len {
-            let sugg = *suggs.data.offset(i as isize);
-            CString::from_raw(sugg as *mut c_char);
+            let sugg = &*suggs.data.offset(i as isize);
+            CString::from_raw(sugg.replacement as *mut c_char);
         }
     }
 }
@@ -75,18 +171,83 @@ unsafe extern "C" fn free_annotations(ptr: *mut AnnotationArray) {
 unsafe extern "C" fn free_provider(ptr: *mut Provider) {
     assert!(!ptr.is_null(), "Trying to clean a NULL value");
     let provider = Box::from_raw(ptr);
-    Box::from_raw(provider.data as *mut Broker);
+    drop(Box::from_raw(provider.data as *mut State));
+}
+
+extern "C" fn list_rules(_props: *const Properties, _data: *mut c_void) -> *mut RuleArray {
+    let rules = vec![RuleInfo {
+        category: static_cstr!("Spelling"),
+        ..RuleInfo::new(
+            static_cstr!("ENCHANT_SPELLING"),
+            static_cstr!("Words not found in the dictionary"),
+        )
+    }];
+    Box::into_raw(Box::new(rules.into()))
+}
+
+unsafe extern "C" fn free_rules(ptr: *mut RuleArray) {
+    drop(Box::from_raw(ptr));
+}
+
+/// Directory with Enchant’s personal word lists.
+fn personal_directory() -> Option<PathBuf> {
+    if let Some(directory) = env::var_os("ENCHANT_CONFIG_DIR") {
+        return Some(PathBuf::from(directory));
+    }
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|config| config.join("enchant"))
+}
+
+unsafe extern "C" fn fingerprint(data: *mut c_void) -> *const c_char {
+    (*(data as *mut State)).fingerprint.as_ptr()
+}
+
+unsafe fn change_words(data: *mut c_void, change: WordChange) {
+    (*(data as *mut State)).change_words(change);
+}
+
+unsafe extern "C" fn add_word(word: *const c_char, data: *mut c_void) {
+    let word = CStr::from_ptr(word).to_string_lossy().into_owned();
+    change_words(data, WordChange::Add(word));
+}
+
+unsafe extern "C" fn remove_word(word: *const c_char, data: *mut c_void) {
+    let word = CStr::from_ptr(word).to_string_lossy().into_owned();
+    change_words(data, WordChange::Remove(word));
+}
+
+unsafe extern "C" fn ignore_word(word: *const c_char, data: *mut c_void) {
+    let word = CStr::from_ptr(word).to_string_lossy().into_owned();
+    change_words(data, WordChange::Ignore(word));
 }
 
 #[no_mangle]
 pub extern "C" fn patronus_provider_init() -> *mut Provider {
-    let broker: *mut Broker = Box::into_raw(Box::new(Broker::new()));
+    let mut state = State {
+        dicts: HashMap::new(),
+        broker: Broker::new(),
+        changes: Vec::new(),
+        fingerprint: CString::default(),
+    };
+    state.update_fingerprint();
+    let state: *mut State = Box::into_raw(Box::new(state));
 
+    // The broker caches dictionaries and cannot be shared so the provider is not thread-safe.
     Box::into_raw(Box::new(Provider {
-        name: get_name,
-        check: check_text,
-        free_annotations: free_annotations,
-        free_provider: free_provider,
-        data: broker as *mut c_void,
+        fingerprint: Some(fingerprint),
+        add_word: Some(add_word),
+        remove_word: Some(remove_word),
+        ignore_word: Some(ignore_word),
+        list_rules: Some(list_rules),
+        free_rules: Some(free_rules),
+        ..Provider::new(
+            get_name,
+            check_text,
+            free_annotations,
+            free_provider,
+            state as *mut c_void,
+        )
     }))
 }
@@ -5,14 +5,34 @@ extern crate xdg;
 
 use languagetool::{LanguageTool, Request, Response};
 use patronus_provider::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
 
 const CONFIG_INSTANCE_URL: &str = "providers.languagetool.instance_url";
+const CONFIG_MAX_TEXT_LENGTH: &str = "providers.languagetool.max_text_length";
+
+/// Rule reported in a match.
+struct SeenRule {
+    description: String,
+    category: String,
+}
+
+/// Data kept between calls.
+struct State {
+    lt: LanguageTool,
+    /// Rules that reported matches, keyed by language and rule ID.
+    seen_rules: HashMap<String, BTreeMap<String, SeenRule>>,
+    /// Identifies the configuration for caching purposes.
+    fingerprint: CString,
+    /// Words Patronus taught us, their misspelling matches are dropped.
+    known_words: HashSet<String>,
+}
 
 #[no_mangle]
 pub extern "C" fn patronus_provider_version() -> c_int {
-    1
+    PROVIDER_VERSION
 }
 
 #[no_mangle]
@@ -20,12 +40,51 @@ pub extern "C" fn get_name() -> *const c_char {
     static_cstr!("Language Tool")
 }
 
+fn to_c_string(s: String) -> *const c_char {
+    CString::new(s).expect("cannot create C string").into_raw()
+}
+
+fn to_optional_c_string(s: Option<String>) -> *const c_char {
+    s.map_or(ptr::null(), to_c_string)
+}
+
+unsafe fn free_optional_c_string(s: *const c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s as *mut c_char));
+    }
+}
+
+/// Maps LanguageTool’s `issueType` onto annotation kind so that misspellings
+/// can be compared with those from other providers.
+fn kind(issue_type: &str) -> AnnotationKind {
+    match issue_type {
+        "misspelling" => AnnotationKind::Spelling,
+        "typographical" | "whitespace" | "characters" => AnnotationKind::Typography,
+        "style" | "register" => AnnotationKind::Style,
+        _ => AnnotationKind::Grammar,
+    }
+}
+
+/// Maps LanguageTool’s `issueType` (based on Localization Quality Issue Type) onto severity.
+fn severity(issue_type: &str) -> Severity {
+    match issue_type {
+        "misspelling" | "grammar" | "locale-violation" | "mistranslation" | "untranslated" => {
+            Severity::Error
+        }
+        "typographical" | "duplication" | "inconsistency" | "whitespace" | "characters"
+        | "terminology" | "omission" | "addition" | "formatting" | "non-conformance" => {
+            Severity::Warning
+        }
+        _ => Severity::Hint,
+    }
+}
+
 extern "C" fn check_text(
     props: *const Properties,
     text: *const c_char,
     data: *mut c_void,
 ) -> *mut AnnotationArray {
-    let lt = unsafe { &mut *(data as *mut LanguageTool) };
+    let state = unsafe { &mut *(data as *mut State) };
 
     let lang = unsafe {
         CStr::from_ptr((*props).primary_language)
@@ -34,39 +93,69 @@ extern "C" fn check_text(
     };
     let text = unsafe { CStr::from_ptr(text).to_string_lossy().into_owned() };
 
-    let req = Request::new(text, lang);
+    let mut req = Request::new(text.clone(), lang.clone());
+    let (disabled_rules, enabled_rules) =
+        unsafe { ((*props).disabled_rules(), (*props).enabled_rules()) };
+    if !disabled_rules.is_empty() {
+        req.disabled_rules = Some(disabled_rules.join(","));
+    }
+    if !enabled_rules.is_empty() {
+        req.enabled_rules = Some(enabled_rules.join(","));
+    }
 
+    let known_words = &state.known_words;
+    let seen_rules = state.seen_rules.entry(lang).or_default();
     let anns = {
         if let Ok(Response {
             matches: Some(matches),
             ..
-        }) = lt.check(req)
+        }) = state.lt.check(req)
         {
             matches
                 .into_iter()
-                .map(|mtch| {
-                    let offset = mtch.offset as usize;
-                    let length = mtch.length as usize;
+                .filter_map(|mtch| {
+                    // LanguageTool measures text in UTF-16 code units.
+                    let range = segment::utf16_to_byte_range(
+                        &text,
+                        mtch.offset as usize,
+                        mtch.length as usize,
+                    );
+                    seen_rules
+                        .entry(mtch.rule.id.clone())
+                        .or_insert_with(|| SeenRule {
+                            description: mtch.rule.description.clone(),
+                            category: mtch.rule.category.name.clone(),
+                        });
+                    if mtch.rule.issue_type == "misspelling"
+                        && known_words.contains(&text[range.clone()])
+                    {
+                        return None;
+                    }
                     let suggestions: Vec<Suggestion> = mtch
                         .replacements
                         .into_iter()
                         .filter_map(|replacement| replacement.value)
-                        .map(|sugg| {
-                            CString::new(sugg)
-                                .expect("cannot create C string")
-                                .into_raw() as *const c_char
-                        })
+                        .map(|sugg| Suggestion::new(to_c_string(sugg)))
                         .collect();
+                    let url = mtch
+                        .rule
+                        .urls
+                        .and_then(|urls| urls.into_iter().filter_map(|url| url.value).next());
+                    // LanguageTool does not report how sure it is, so the confidence stays unknown.
                     let ann = Annotation {
-                        offset: offset,
-                        length: length,
-                        message: CString::new(mtch.message)
-                            .expect("cannot create C string")
-                            .into_raw(),
-                        kind: AnnotationKind::Grammar,
-                        suggestions: Box::into_raw(Box::new(suggestions.into())),
+                        rule_id: to_c_string(mtch.rule.id),
+                        category: to_c_string(mtch.rule.category.name),
+                        url: to_optional_c_string(url),
+                        severity: severity(&mtch.rule.issue_type),
+                        ..Annotation::new(
+                            range.start,
+                            range.len(),
+                            to_c_string(mtch.message),
+                            kind(&mtch.rule.issue_type),
+                            Box::into_raw(Box::new(suggestions.into())),
+                        )
                     };
-                    ann as Annotation
+                    Some(ann)
                 })
                 .collect::<Vec<Annotation>>()
         } else {
@@ -82,17 +171,73 @@ unsafe extern "C" fn free_annotations(ptr: *mut AnnotationArray) {
         let ann = &*anns.data.offset(i as isize);
         let suggs = Box::from_raw(ann.suggestions);
         for i in 0..suggs.len {
-            let sugg = *suggs.data.offset(i as isize);
-            CString::from_raw(sugg as *mut c_char);
+            let sugg = &*suggs.data.offset(i as isize);
+            CString::from_raw(sugg.replacement as *mut c_char);
         }
         CString::from_raw(ann.message as *mut c_char);
+        free_optional_c_string(ann.rule_id);
+        free_optional_c_string(ann.category);
+        free_optional_c_string(ann.url);
     }
 }
 
 unsafe extern "C" fn free_provider(ptr: *mut Provider) {
     assert!(!ptr.is_null(), "Trying to clean a NULL value");
     let provider = Box::from_raw(ptr);
-    Box::from_raw(provider.data as *mut LanguageTool);
+    drop(Box::from_raw(provider.data as *mut State));
+}
+
+/// Lists the rules that reported matches in texts of given language so far.
+///
+/// The documented API has no endpoint listing the rules, so they are learnt from the checks.
+unsafe extern "C" fn list_rules(props: *const Properties, data: *mut c_void) -> *mut RuleArray {
+    let state = &*(data as *const State);
+    let lang = CStr::from_ptr((*props).primary_language).to_string_lossy();
+    let rules: Vec<RuleInfo> = state
+        .seen_rules
+        .get(&*lang)
+        .into_iter()
+        .flatten()
+        .map(|(id, rule)| RuleInfo {
+            category: to_c_string(rule.category.clone()),
+            ..RuleInfo::new(
+                to_c_string(id.clone()),
+                to_c_string(rule.description.clone()),
+            )
+        })
+        .collect();
+    Box::into_raw(Box::new(rules.into()))
+}
+
+unsafe extern "C" fn free_rules(ptr: *mut RuleArray) {
+    let rules = Box::from_raw(ptr);
+    for i in 0..rules.len {
+        let rule = &*rules.data.add(i);
+        drop(CString::from_raw(rule.id as *mut c_char));
+        drop(CString::from_raw(rule.description as *mut c_char));
+        free_optional_c_string(rule.category);
+    }
+}
+
+unsafe fn known_words<'a>(data: *mut c_void) -> &'a mut HashSet<String> {
+    &mut (*(data as *mut State)).known_words
+}
+
+/// The `/v2/check` endpoint cannot be given words to accept with a request,
+/// its `dicts` parameter only names dictionaries stored on the server for a premium account
+/// and filled through `/v2/words/add`. Words are therefore kept for the session
+/// and their misspelling matches are dropped from the response.
+unsafe extern "C" fn add_word(word: *const c_char, data: *mut c_void) {
+    let word = CStr::from_ptr(word).to_string_lossy().into_owned();
+    known_words(data).insert(word);
+}
+
+unsafe extern "C" fn remove_word(word: *const c_char, data: *mut c_void) {
+    known_words(data).remove(&*CStr::from_ptr(word).to_string_lossy());
+}
+
+unsafe extern "C" fn fingerprint(data: *mut c_void) -> *const c_char {
+    (*(data as *mut State)).fingerprint.as_ptr()
 }
 
 #[no_mangle]
@@ -100,6 +245,9 @@ pub extern "C" fn patronus_provider_init() -> *mut Provider {
     let mut c = config::Config::new();
     c.set_default(CONFIG_INSTANCE_URL, "http://localhost:8081/")
         .expect("Cannot set default value for instance url.");
+    // Public instance limits the request size, self-hosted ones usually do not.
+    c.set_default(CONFIG_MAX_TEXT_LENGTH, 0)
+        .expect("Cannot set default value for maximum text length.");
     if let Ok(xdg_dirs) = xdg::BaseDirectories::with_prefix("patronus") {
         if let Some(path) = xdg_dirs.find_config_file("config.toml") {
             let user_config = config::File::new(&path.to_string_lossy(), config::FileFormat::Toml)
@@ -112,19 +260,42 @@ pub extern "C" fn patronus_provider_init() -> *mut Provider {
     let instance_url = c
         .get_str(CONFIG_INSTANCE_URL)
         .expect("Could not determine instance URL.");
+    let max_text_length = c
+        .get_int(CONFIG_MAX_TEXT_LENGTH)
+        .expect("Could not determine maximum text length.");
     match LanguageTool::new(&instance_url) {
         Err(msg) => {
             panic!("Cannot create Language Tool instance: {}", msg);
         }
         Ok(lt) => {
-            let lt: *mut LanguageTool = Box::into_raw(Box::new(lt));
+            let fingerprint = CString::new(format!(
+                "{} {}",
+                env!("CARGO_PKG_VERSION"),
+                instance_url
+            ))
+            .expect("cannot create C string");
+            let state: *mut State = Box::into_raw(Box::new(State {
+                lt,
+                seen_rules: HashMap::new(),
+                fingerprint,
+                known_words: HashSet::new(),
+            }));
 
             Box::into_raw(Box::new(Provider {
-                name: get_name,
-                check: check_text,
-                free_annotations: free_annotations,
-                free_provider: free_provider,
-                data: lt as *mut c_void,
+                fingerprint: Some(fingerprint),
+                max_text_length: max_text_length.max(0) as usize,
+                add_word: Some(add_word),
+                remove_word: Some(remove_word),
+                ignore_word: Some(add_word),
+                list_rules: Some(list_rules),
+                free_rules: Some(free_rules),
+                ..Provider::new(
+                    get_name,
+                    check_text,
+                    free_annotations,
+                    free_provider,
+                    state as *mut c_void,
+                )
             }))
         }
     }
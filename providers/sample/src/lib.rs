@@ -12,9 +12,9 @@ use std::os::raw::{c_char, c_int, c_void};
 // it and also set force the function to use C calling conventions.
 #[no_mangle]
 pub extern "C" fn patronus_provider_version() -> c_int {
-    // Simply return 1, Rust returns the last statement
-    // in the function
-    1
+    // Simply return the version of the interface we implement,
+    // Rust returns the last statement in the function
+    PROVIDER_VERSION
 }
 
 #[no_mangle]
@@ -40,16 +40,34 @@ fn check_text_english(text: Cow<str>) -> *mut AnnotationArray {
         .map(|(offset, text)| {
             // Just preparing some data structures.
             let length = text.len() as usize;
+            // Suggestions can carry a short description and our confidence.
             let suggestions = vec![
-                static_cstr!("mistakes are never good"),
-                static_cstr!("mistakes are bad"),
+                Suggestion {
+                    description: static_cstr!("Stronger statement"),
+                    confidence: 0.4,
+                    ..Suggestion::new(static_cstr!("mistakes are never good"))
+                },
+                Suggestion {
+                    confidence: 0.6,
+                    ..Suggestion::new(static_cstr!("mistakes are bad"))
+                },
             ].into();
             Annotation {
-                offset: offset,
-                length: length,
-                message: static_cstr!("Are you sure about mistakes being good?"),
-                kind: AnnotationKind::Suggestion,
-                suggestions: Box::into_raw(Box::new(suggestions)),
+                // Rule identifier allows users to turn off the rule.
+                rule_id: static_cstr!("MISTAKES_ARE_GOOD"),
+                category: static_cstr!("Semantics"),
+                // It is just a matter of opinion but we are quite sure.
+                severity: Severity::Hint,
+                confidence: 0.9,
+                // We have no documentation to link to and Patronus knows
+                // which provider we are so `url` and `provider` stay null.
+                ..Annotation::new(
+                    offset,
+                    length,
+                    static_cstr!("Are you sure about mistakes being good?"),
+                    AnnotationKind::Suggestion,
+                    Box::into_raw(Box::new(suggestions)),
+                )
             }
         })
         .collect();
@@ -73,6 +91,12 @@ extern "C" fn check_text(
             .to_string_lossy()
             .into_owned()
     };
+    // Patronus would hide the annotations of a rule the user turned off
+    // but we can save ourselves the work.
+    let disabled_rules = unsafe { (*props).disabled_rules() };
+    if disabled_rules.iter().any(|rule| rule == "MISTAKES_ARE_GOOD") {
+        return Box::into_raw(Box::new(Vec::new().into()));
+    }
     // Same here but we do not need the ownership.
     let text = unsafe { CStr::from_ptr(text).to_string_lossy() };
     // We remove the country code, our checker is good for any English.
@@ -104,16 +128,43 @@ unsafe extern "C" fn free_provider(ptr: *mut Provider) {
     let _provider = Box::from_raw(ptr);
 }
 
+/// Describes the rules we check so that users can find out what to turn off.
+extern "C" fn list_rules(_props: *const Properties, _data: *mut c_void) -> *mut RuleArray {
+    let rules = vec![RuleInfo {
+        category: static_cstr!("Semantics"),
+        ..RuleInfo::new(
+            static_cstr!("MISTAKES_ARE_GOOD"),
+            static_cstr!("Doubts claims that mistakes are good"),
+        )
+    }];
+    Box::into_raw(Box::new(rules.into()))
+}
+
+unsafe extern "C" fn free_rules(ptr: *mut RuleArray) {
+    // The strings are static so only the array needs to be freed.
+    drop(Box::from_raw(ptr));
+}
+
 /// Initialize the provider with the functions
 #[no_mangle]
 pub extern "C" fn patronus_provider_init() -> *mut Provider {
     Box::into_raw(Box::new(Provider {
-        name: get_name,
-        check: check_text,
-        free_annotations: free_annotations,
-        free_provider: free_provider,
-        // We do not really need to store anything here, so we just fill in null
-        data: std::ptr::null_mut(),
+        // We can check texts of any length and, since we do not keep any state,
+        // even several of them at once.
+        thread_safe: true,
+        list_rules: Some(list_rules),
+        free_rules: Some(free_rules),
+        // The results only depend on the version of the library so Patronus does not need
+        // a fingerprint to know when to discard cached ones. There is no dictionary to teach
+        // new words either, Patronus hides annotations of the words it knows by itself.
+        ..Provider::new(
+            get_name,
+            check_text,
+            free_annotations,
+            free_provider,
+            // We do not really need to store anything here, so we just fill in null
+            std::ptr::null_mut(),
+        )
     }))
 }
 